@@ -0,0 +1,39 @@
+use shaku::{Component, Interface};
+
+use crate::channels::{Channels, Subscriber};
+use crate::handlers::groups::UserGroup;
+use crate::repos::Id;
+
+/// Set of user groups, the default one if `set_name` is `None`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SetKey {
+    pub user_id: Id,
+    pub set_name: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum GroupEvent {
+    /// group changed, e.g. renamed, `UserGroup` is as it is seen in the set
+    Updated(UserGroup),
+}
+
+pub trait GroupsChannelIf: Interface {
+    fn publish(&self, set: &SetKey, event: GroupEvent);
+    fn subscribe(&self, set: &SetKey) -> Subscriber<SetKey, GroupEvent>;
+}
+
+#[shaku(interface = GroupsChannelIf)]
+#[derive(Component)]
+pub struct GroupsChannel {
+    channels: Channels<SetKey, GroupEvent>,
+}
+
+impl GroupsChannelIf for GroupsChannel {
+    fn publish(&self, set: &SetKey, event: GroupEvent) {
+        self.channels.publish(set, event);
+    }
+
+    fn subscribe(&self, set: &SetKey) -> Subscriber<SetKey, GroupEvent> {
+        self.channels.subscribe(set.clone())
+    }
+}
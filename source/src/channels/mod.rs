@@ -0,0 +1,109 @@
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures::task::{Context, Poll};
+use futures::{Stream, StreamExt};
+use fxhash::FxHashMap;
+use slab::Slab;
+
+pub mod groups;
+pub mod sessions;
+pub mod stack;
+
+type Senders<K, T> = Arc<Mutex<FxHashMap<K, Slab<UnboundedSender<T>>>>>;
+
+///
+/// Рассылает сообщения всем подписчикам ключа,
+/// например всем открытым вкладкам пользователя
+///
+pub struct Channels<K, T> {
+    senders: Senders<K, T>,
+}
+
+impl<K, T> Default for Channels<K, T>
+where
+    K: Hash + Eq,
+{
+    fn default() -> Self {
+        Channels {
+            senders: Default::default(),
+        }
+    }
+}
+
+impl<K, T> Channels<K, T>
+where
+    K: Hash + Eq + Clone + Unpin,
+    T: Clone,
+{
+    pub fn publish(&self, key: &K, msg: T) {
+        let mut map = self.senders.lock().unwrap();
+
+        if let Some(senders) = map.get_mut(key) {
+            for (_, sender) in senders.iter_mut() {
+                // receiver may be already dropped, it is removed from senders right after that
+                sender.unbounded_send(msg.clone()).ok();
+            }
+        }
+    }
+
+    pub fn subscribe(&self, key: K) -> Subscriber<K, T> {
+        let mut map = self.senders.lock().unwrap();
+
+        let (tx, rx) = mpsc::unbounded();
+        let idx = map
+            .entry(key.clone())
+            .or_insert_with(Default::default)
+            .insert(tx);
+
+        Subscriber {
+            senders: self.senders.clone(),
+            key,
+            idx,
+            receiver: rx,
+        }
+    }
+}
+
+///
+/// Стрим сообщений по ключу, при дропе отписывается
+///
+pub struct Subscriber<K, T>
+where
+    K: Hash + Eq,
+{
+    senders: Senders<K, T>,
+    key: K,
+    // индекс куда в slab вставлен sender, чтобы потом в дропе удалять из slab
+    idx: usize,
+    receiver: UnboundedReceiver<T>,
+}
+
+impl<K, T> Drop for Subscriber<K, T>
+where
+    K: Hash + Eq,
+{
+    fn drop(&mut self) {
+        let mut map = self.senders.lock().unwrap();
+
+        if let Some(senders) = map.get_mut(&self.key) {
+            senders.remove(self.idx);
+            if senders.is_empty() {
+                map.remove(&self.key);
+            }
+        }
+    }
+}
+
+impl<K, T> Stream for Subscriber<K, T>
+where
+    K: Hash + Eq + Unpin,
+{
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
@@ -0,0 +1,32 @@
+use shaku::{Component, Interface};
+
+use crate::channels::{Channels, Subscriber};
+use crate::repos::Id;
+
+#[derive(Debug, Clone)]
+pub enum SessionEvent {
+    /// some of user token pairs were revoked, e.g. by logout,
+    /// connections authenticated with them have to be closed
+    Revoked,
+}
+
+pub trait SessionsChannelIf: Interface {
+    fn publish(&self, user_id: &Id, event: SessionEvent);
+    fn subscribe(&self, user_id: &Id) -> Subscriber<Id, SessionEvent>;
+}
+
+#[shaku(interface = SessionsChannelIf)]
+#[derive(Component)]
+pub struct SessionsChannel {
+    channels: Channels<Id, SessionEvent>,
+}
+
+impl SessionsChannelIf for SessionsChannel {
+    fn publish(&self, user_id: &Id, event: SessionEvent) {
+        self.channels.publish(user_id, event);
+    }
+
+    fn subscribe(&self, user_id: &Id) -> Subscriber<Id, SessionEvent> {
+        self.channels.subscribe(user_id.clone())
+    }
+}
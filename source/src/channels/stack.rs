@@ -0,0 +1,35 @@
+use shaku::{Component, Interface};
+
+use crate::channels::{Channels, Subscriber};
+use crate::repos::Id;
+use crate::services::stack::StackItem;
+
+#[derive(Debug, Clone)]
+pub enum StackItemEvent {
+    /// item appeared in user stack, also sent when item is restored from trash
+    Created(StackItem),
+    Updated(StackItem),
+    /// item left user stack, also sent when item is moved to trash
+    Deleted(Id),
+}
+
+pub trait StackChannelIf: Interface {
+    fn publish(&self, user_id: &Id, event: StackItemEvent);
+    fn subscribe(&self, user_id: &Id) -> Subscriber<Id, StackItemEvent>;
+}
+
+#[shaku(interface = StackChannelIf)]
+#[derive(Component)]
+pub struct StackChannel {
+    channels: Channels<Id, StackItemEvent>,
+}
+
+impl StackChannelIf for StackChannel {
+    fn publish(&self, user_id: &Id, event: StackItemEvent) {
+        self.channels.publish(user_id, event);
+    }
+
+    fn subscribe(&self, user_id: &Id) -> Subscriber<Id, StackItemEvent> {
+        self.channels.subscribe(user_id.clone())
+    }
+}
@@ -29,10 +29,15 @@ pub struct Config {
 
     pub pwd_min_len: u32,
 
+    /// how many recently used sets are kept for a user
+    pub recent_sets_max: u32,
+
     #[shaku(no_default)]
     pub access_token_lifetime: Duration,
     #[shaku(no_default)]
     pub refresh_token_lifetime: Duration,
+    /// key tokens are hashed with before they are stored
+    pub token_secret: String,
 
     pub clear_logger_files: bool,
     pub loggers_json_pretty: bool,
@@ -54,6 +59,8 @@ impl Config {
         Config {
             pwd_min_len: 6,
 
+            recent_sets_max: 10,
+
             access_token_lifetime: Duration::hours(1),
             refresh_token_lifetime: Duration::days(14),
 
@@ -85,6 +92,8 @@ impl Config {
 
             db_name: read_var("MONGO_DB_NAME"),
 
+            token_secret: read_var("TOKEN_SECRET"),
+
             clear_logger_files: read_var("CLEAR_LOGGERS_FILES")
                 .parse()
                 .expect("CLEAR_LOGGERS_FILES must be valid bool (true/false)"),
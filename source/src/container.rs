@@ -1,13 +1,16 @@
 use shaku::module;
 
+use crate::channels::groups::GroupsChannel;
+use crate::channels::sessions::SessionsChannel;
+use crate::channels::stack::StackChannel;
 use crate::config::Config;
 use crate::db::DB;
 use crate::logger::AppLogger;
 use crate::repos::blocks::BlocksRepo;
 use crate::repos::default_group_sets::DefaultGroupSetsRepo;
+use crate::repos::group_items::GroupItemsRepo;
 use crate::repos::group_sets::GroupSetsRepo;
 use crate::repos::groups::GroupsRepo;
-use crate::repos::groups_ordering::GroupsOrderingRepo;
 use crate::repos::marks::MarksRepo;
 use crate::repos::recent_sets::RecentSetsRepo;
 use crate::repos::sets::SetsRepo;
@@ -27,11 +30,16 @@ module! {
             Config,
             AppLogger,
 
+            // channel
+            GroupsChannel,
+            SessionsChannel,
+            StackChannel,
+
             // repo
             BlocksRepo,
             DefaultGroupSetsRepo,
             GroupsRepo,
-            GroupsOrderingRepo,
+            GroupItemsRepo,
             GroupSetsRepo,
             MarksRepo,
             RecentSetsRepo,
@@ -11,6 +11,8 @@ pub enum AppErrorType {
     AccessExpired,
     InternalServerError,
     ValidationError,
+    /// changes were made against outdated version of the object
+    Conflict,
     General,
 }
 
@@ -22,6 +24,7 @@ impl ToString for AppErrorType {
             AccessExpired => "access_expired",
             InternalServerError => "internal_server_error",
             ValidationError => "validation_error",
+            Conflict => "conflict",
             General => "general_error",
         }
         .to_string()
@@ -32,6 +35,8 @@ impl ToString for AppErrorType {
 pub struct AppError {
     message: String,
     error_type: AppErrorType,
+    field: Option<String>,
+    current_version: Option<i32>,
 }
 
 impl AppError {
@@ -39,6 +44,8 @@ impl AppError {
         AppError {
             message: message.to_string(),
             error_type,
+            field: None,
+            current_version: None,
         }
     }
 
@@ -62,6 +69,24 @@ impl AppError {
         AppError::new(message, AppErrorType::ValidationError)
     }
 
+    /// Validation error of the particular input field,
+    /// `field` is a path to it like `blocks.0.marks.1.to`
+    pub fn field_validation(field: &str, message: &str) -> AppError {
+        AppError {
+            field: Some(field.to_string()),
+            ..AppError::validation(message)
+        }
+    }
+
+    /// `current_version` is the version of the object on the server,
+    /// client should reload the object and apply its changes again
+    pub fn conflict(message: &str, current_version: i32) -> AppError {
+        AppError {
+            current_version: Some(current_version),
+            ..AppError::new(message, AppErrorType::Conflict)
+        }
+    }
+
     pub fn internal() -> AppError {
         AppError::new("internal server error", AppErrorType::InternalServerError)
     }
@@ -73,6 +98,14 @@ impl AppError {
     pub fn get_type(&self) -> String {
         self.error_type.to_string()
     }
+
+    pub fn get_field(&self) -> Option<String> {
+        self.field.clone()
+    }
+
+    pub fn get_current_version(&self) -> Option<i32> {
+        self.current_version
+    }
 }
 
 impl fmt::Display for AppError {
@@ -0,0 +1,98 @@
+use actix_web::dev::Payload;
+use actix_web::http::header;
+use actix_web::{FromRequest, HttpRequest};
+use async_graphql::guard::Guard;
+use async_graphql::{Context, Result};
+use async_trait::async_trait;
+use futures::future::{ok, Ready};
+use std::net::SocketAddr;
+
+use crate::errors::AppError;
+use crate::repos::tokens::SessionMeta;
+use crate::repos::users::User;
+use crate::utils::ExtendType;
+
+/// Access token from `Authorization: Bearer <token>` request header
+pub struct BearerToken(pub Option<String>);
+
+impl FromRequest for BearerToken {
+    type Error = actix_web::Error;
+    type Future = Ready<std::result::Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty());
+
+        ok(BearerToken(token))
+    }
+}
+
+/// Access token the request is authenticated with, it is in request data along with `User`
+pub struct CurrentAccess(pub String);
+
+/// Client user agent and address to show in the user sessions list
+pub fn session_meta(req: &HttpRequest) -> SessionMeta {
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|h| h.to_str().ok())
+        .map(|ua| ua.to_string());
+    // realip_remote_addr может быть с портом или без
+    let ip = req.connection_info().realip_remote_addr().map(|addr| {
+        addr.parse::<SocketAddr>()
+            .map(|a| a.ip().to_string())
+            .unwrap_or(addr.to_string())
+    });
+
+    SessionMeta { user_agent, ip }
+}
+
+///
+/// Пропускает к полю только запросы с валидным токеном.
+/// `graphql` хендлер кладёт в данные запроса `User` если токен валидный
+/// или `AppError` почему он не валидный
+///
+pub struct AuthGuard;
+
+#[async_trait]
+impl Guard for AuthGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        if ctx.data_opt::<User>().is_some() {
+            return Ok(());
+        }
+
+        let err = ctx
+            .data_opt::<AppError>()
+            .cloned()
+            .unwrap_or(AppError::unauthorized());
+        Err::<(), _>(err).extend_type()
+    }
+}
+
+/// User the request is made by, fields calling it must be guarded with `AuthGuard`
+pub fn current_user(ctx: &Context<'_>) -> User {
+    ctx.data_unchecked::<User>().clone()
+}
+
+///
+/// Токен из payload `connection_init` сообщения вебсокета,
+/// `{"Authorization": "Bearer <token>"}` как заголовок или `{"access": "<token>"}`
+///
+pub fn init_token(payload: &serde_json::Value) -> Option<String> {
+    let header = payload
+        .get("Authorization")
+        .or_else(|| payload.get("authorization"))
+        .and_then(|h| h.as_str())
+        .and_then(|h| h.strip_prefix("Bearer "));
+
+    header
+        .or_else(|| payload.get("access").and_then(|a| a.as_str()))
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
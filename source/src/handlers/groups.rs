@@ -1,6 +1,7 @@
-use async_graphql::{Object, SimpleObject};
+use async_graphql::{Enum, Object, SimpleObject};
 use serde::Serialize;
 
+use crate::channels::groups::GroupEvent;
 use crate::repos::Id;
 use crate::services::Paged;
 
@@ -18,8 +19,32 @@ pub struct UserGroup {
     pub order: i32,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, SimpleObject)]
 pub struct RemovedGroup {
     pub id: Id,
     pub name: String,
 }
+
+#[derive(Enum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GroupChangeType {
+    Updated,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct GroupChange {
+    pub change_type: GroupChangeType,
+    pub id: Id,
+    pub group: UserGroup,
+}
+
+impl From<GroupEvent> for GroupChange {
+    fn from(event: GroupEvent) -> Self {
+        match event {
+            GroupEvent::Updated(group) => GroupChange {
+                change_type: GroupChangeType::Updated,
+                id: group.id.clone(),
+                group,
+            },
+        }
+    }
+}
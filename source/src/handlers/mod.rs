@@ -5,18 +5,25 @@ use actix_web::Result;
 use actix_web_actors::ws;
 use async_graphql::{InputObject, Schema};
 use async_graphql::http::{GraphQLPlaygroundConfig, playground_source};
-use async_graphql_actix_web::{Request, Response, WSSubscription};
+use async_graphql_actix_web::{Request, Response};
+use chrono::Utc;
 
 use crate::container::Container;
+use crate::channels::sessions::SessionsChannelIf;
+use crate::handlers::auth::{session_meta, BearerToken, CurrentAccess};
 use crate::handlers::mutation::Mutation;
 use crate::handlers::query::Query;
 use crate::handlers::subscription::Subscription;
+use crate::handlers::websocket::AuthWSSubscription;
+use crate::services::auth::AuthServiceIf;
 
+pub mod auth;
 pub mod groups;
 pub mod mutation;
 pub mod query;
 pub mod stack;
 pub mod subscription;
+pub mod websocket;
 
 #[derive(InputObject)]
 pub struct Paging {
@@ -31,17 +38,41 @@ pub struct Context {
 
 pub type Root = Schema<Query, Mutation, Subscription>;
 
-pub async fn graphql(schema: web::Data<Root>, req: Request) -> Response {
-    schema.execute(req.into_inner()).await.into()
+///
+/// Пользователь по токену из заголовка `Authorization` кладётся в данные запроса,
+/// если токен не валидный - ошибка почему, её вернут поля под `AuthGuard`
+///
+pub async fn graphql(
+    schema: web::Data<Root>,
+    auth: web::Data<Arc<dyn AuthServiceIf>>,
+    token: BearerToken,
+    http: HttpRequest,
+    req: Request,
+) -> Response {
+    let mut req = req.into_inner().data(session_meta(&http));
+    if let Some(access) = token.0 {
+        req = match auth.validate_access(&access, Utc::now()).await {
+            Ok(user) => req.data(user).data(CurrentAccess(access)),
+            Err(e) => req.data(e),
+        };
+    }
+
+    schema.execute(req).await.into()
 }
 
 pub async fn graphql_subscriptions(
     schema: web::Data<Root>,
+    auth: web::Data<Arc<dyn AuthServiceIf>>,
+    sessions_channel: web::Data<Arc<dyn SessionsChannelIf>>,
     req: HttpRequest,
     payload: web::Payload,
 ) -> Result<HttpResponse> {
     ws::start_with_protocols(
-        WSSubscription::new(Schema::clone(&*schema)),
+        AuthWSSubscription::new(
+            Schema::clone(&*schema),
+            Arc::clone(&*auth),
+            Arc::clone(&*sessions_channel),
+        ),
         &["graphql-ws"],
         &req,
         payload,
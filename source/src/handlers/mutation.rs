@@ -1,3 +1,4 @@
+use async_graphql::guard::Guard;
 use async_graphql::Result;
 use async_graphql::{Context, Object};
 use chrono::Utc;
@@ -5,11 +6,14 @@ use shaku::HasComponent;
 
 use crate::config::ConfigIf;
 use crate::container::Container;
-use crate::handlers::groups::{UserGroup, UserSet};
-use crate::repos::tokens::TokenPair;
+use crate::handlers::auth::{current_user, AuthGuard, CurrentAccess};
+use crate::handlers::groups::{RemovedGroup, UserGroup, UserSet};
+use crate::handlers::stack::{NewStackItem, StackItem, StackItemChangeSet};
+use crate::repos::tokens::{SessionMeta, TokenPair};
 use crate::repos::Id;
 use crate::services::auth::AuthServiceIf;
 use crate::services::groups::{GroupsServiceIf, IntoSet};
+use crate::services::stack::StackServiceIf;
 use crate::utils::ExtendType;
 
 pub struct Mutation;
@@ -43,7 +47,8 @@ impl Mutation {
     ) -> Result<TokenPair> {
         let ctr: &Container = ctx.data_unchecked::<Container>();
         let auth: &dyn AuthServiceIf = ctr.resolve_ref();
-        auth.login(username, password, Utc::now())
+        let meta = ctx.data_opt::<SessionMeta>().cloned().unwrap_or_default();
+        auth.login(username, password, meta, Utc::now())
             .await
             .extend_type()
     }
@@ -55,64 +60,296 @@ impl Mutation {
         auth.refresh_token(&refresh, Utc::now()).await.extend_type()
     }
 
-    pub async fn create_set(
+    /// revokes tokens the request is made with
+    #[graphql(guard(AuthGuard()))]
+    pub async fn logout(&self, ctx: &Context<'_>) -> Result<&str> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let access = ctx.data_unchecked::<CurrentAccess>();
+
+        let auth: &dyn AuthServiceIf = ctr.resolve_ref();
+        auth.logout(&access.0).await.map(|_| "ok").extend_type()
+    }
+
+    /// revokes all user tokens on every device
+    #[graphql(guard(AuthGuard()))]
+    pub async fn logout_everywhere(&self, ctx: &Context<'_>) -> Result<&str> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let auth: &dyn AuthServiceIf = ctr.resolve_ref();
+        auth.logout_everywhere(&user)
+            .await
+            .map(|_| "ok")
+            .extend_type()
+    }
+
+    /// revokes one of `mySessions`
+    #[graphql(guard(AuthGuard()))]
+    pub async fn revoke_session(&self, ctx: &Context<'_>, id: Id) -> Result<&str> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let auth: &dyn AuthServiceIf = ctr.resolve_ref();
+        auth.revoke_session(&user, &id)
+            .await
+            .map(|_| "ok")
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn create_set(&self, ctx: &Context<'_>, set_name: String) -> Result<UserSet> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let groups: &dyn GroupsServiceIf = ctr.resolve_ref();
+        groups
+            .create_set(user, set_name, Utc::now())
+            .await
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn rename_set(&self, ctx: &Context<'_>, id: Id, set_name: String) -> Result<UserSet> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let groups: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+        groups
+            .rename_set(user, id, set_name, Utc::now())
+            .await
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn delete_set(&self, ctx: &Context<'_>, id: Id) -> Result<UserSet> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let groups: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+        groups.delete_set(user, id).await.extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn create_group(
         &self,
         ctx: &Context<'_>,
-        access: String,
-        set_name: String,
-    ) -> Result<UserSet> {
+        group_name: String,
+        group_set: Option<String>,
+        insert_after: Option<Id>,
+    ) -> Result<UserGroup> {
         let ctr: &Container = ctx.data_unchecked::<Container>();
-        let auth: &dyn AuthServiceIf = ctr.resolve_ref();
-        let user = auth.validate_access(&access, Utc::now()).await?;
+        let user = current_user(ctx);
+
+        let groups: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+        groups
+            .create_group(user, group_name, group_set.into(), insert_after, Utc::now())
+            .await
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn remove_group(&self, ctx: &Context<'_>, id: Id) -> Result<RemovedGroup> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let groups: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+        groups
+            .remove_group(user, id, Utc::now())
+            .await
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn rename_group(
+        &self,
+        ctx: &Context<'_>,
+        group_id: Id,
+        group_name: String,
+    ) -> Result<UserGroup> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let groups: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+        groups
+            .rename_group(user, group_id, group_name, Utc::now())
+            .await
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn move_group(
+        &self,
+        ctx: &Context<'_>,
+        group_id: Id,
+        to_set: Option<String>,
+        after_group_id: Option<Id>,
+    ) -> Result<UserGroup> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
 
         let groups: &dyn GroupsServiceIf = ctr.resolve_ref();
-        groups.create_set(user, set_name).await.extend_type()
-    }
-
-    // pub async fn create_group(
-    //     &self,
-    //     ctx: &Context<'_>,
-    //     access: String,
-    //     group_name: String,
-    //     group_set: Option<String>,
-    //     insert_after: Option<Id>,
-    // ) -> Result<UserGroup> {
-    //     let ctr: &Container = ctx.data_unchecked::<Container>();
-    //     let auth: &dyn AuthServiceIf = ctr.resolve_ref();
-    //     let user = auth.validate_access(&access, Utc::now()).await?;
-    //
-    //     let groups: &dyn GroupsServiceIf = ctr.resolve_ref();
-    //
-    //     groups
-    //         .create_group(user, group_name, group_set.into(), insert_after)
-    //         .await
-    //         .extend_type()
-    // }
-
-    // pub async fn my_stack_add(
-    //     access: String,
-    //     stack_item: NewStackItem,
-    //     ctx: &Context,
-    // ) -> AppResult<StackItem> {
-    //     let auth: &dyn AuthServiceIf = ctx.ctr.resolve_ref();
-    //     let user = auth.validate_access(&access, Utc::now()).await?;
-    //
-    //     let stack_service: &dyn StackServiceIf = ctx.ctr.resolve_ref();
-    //     Ok(stack_service
-    //         .add_to_my_stack(user, stack_item)
-    //         .await?
-    //         .into())
-    // }
-    //
-    // pub async fn my_stack_edit(
-    //     access: String,
-    //     changes: StackItemChangeSet,
-    //     ctx: &Context,
-    // ) -> AppResult<StackItem> {
-    //     let auth: &dyn AuthServiceIf = ctx.ctr.resolve_ref();
-    //     let user = auth.validate_access(&access, Utc::now()).await?;
-    //
-    //     let stack_service: &dyn StackServiceIf = ctx.ctr.resolve_ref();
-    //     Ok(stack_service.update_stack_item(user, changes).await?.into())
-    // }
+
+        groups
+            .move_group(user, group_id, to_set.into(), after_group_id, Utc::now())
+            .await
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn my_stack_add(
+        &self,
+        ctx: &Context<'_>,
+        stack_item: NewStackItem,
+    ) -> Result<StackItem> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        stack_service
+            .add_to_my_stack(user, stack_item)
+            .await
+            .map(|i| i.into())
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn my_stack_edit(
+        &self,
+        ctx: &Context<'_>,
+        changes: StackItemChangeSet,
+    ) -> Result<StackItem> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        stack_service
+            .update_stack_item(user, changes)
+            .await
+            .map(|i| i.into())
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn my_stack_remove(&self, ctx: &Context<'_>, stack_id: Id) -> Result<StackItem> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        stack_service
+            .remove_from_my_stack(user, stack_id, Utc::now())
+            .await
+            .map(|i| i.into())
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn stack_pop(&self, ctx: &Context<'_>, into_group: Id) -> Result<StackItem> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        stack_service
+            .stack_pop(user, into_group)
+            .await
+            .map(|i| i.into())
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn stack_push(&self, ctx: &Context<'_>, stack_id: Id) -> Result<StackItem> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        stack_service
+            .stack_push(user, stack_id, Utc::now())
+            .await
+            .map(|i| i.into())
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn my_stack_add_to_group(
+        &self,
+        ctx: &Context<'_>,
+        stack_id: Id,
+        group_id: Id,
+        after_stack_id: Option<Id>,
+    ) -> Result<StackItem> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        stack_service
+            .add_to_group(user, stack_id, group_id, after_stack_id)
+            .await
+            .map(|i| i.into())
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn my_stack_remove_from_group(
+        &self,
+        ctx: &Context<'_>,
+        stack_id: Id,
+        group_id: Id,
+    ) -> Result<StackItem> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        stack_service
+            .remove_from_group(user, stack_id, group_id)
+            .await
+            .map(|i| i.into())
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn trash_restore(&self, ctx: &Context<'_>, stack_id: Id) -> Result<StackItem> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        stack_service
+            .restore_from_trash(user, stack_id)
+            .await
+            .map(|i| i.into())
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn trash_purge(&self, ctx: &Context<'_>, stack_id: Id) -> Result<Id> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        stack_service
+            .purge_from_trash(user, stack_id)
+            .await
+            .extend_type()
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn restore_block_version(
+        &self,
+        ctx: &Context<'_>,
+        block_id: Id,
+        version: i32,
+    ) -> Result<StackItem> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        stack_service
+            .restore_block_version(user, block_id, version)
+            .await
+            .map(|i| i.into())
+            .extend_type()
+    }
 }
@@ -1,4 +1,5 @@
-use async_graphql::connection::{query, Connection, Edge, EmptyFields};
+use async_graphql::connection::{Connection, Edge, EmptyFields};
+use async_graphql::guard::Guard;
 use async_graphql::Result;
 use async_graphql::*;
 use chrono::Utc;
@@ -6,32 +7,81 @@ use shaku::HasComponent;
 
 use crate::config::ConfigIf;
 use crate::container::Container;
+use crate::handlers::auth::{current_user, AuthGuard, CurrentAccess};
 use crate::handlers::groups::{UserGroup, UserSet};
-use crate::handlers::stack::StackItem;
+use crate::handlers::stack::{BlockVersion, SearchHit, StackItem};
 use crate::handlers::Paging;
+use crate::repos::sets::SetsSortBy;
 use crate::repos::Id;
-use crate::services::auth::AuthServiceIf;
 // use crate::services::groups::{GroupsServiceIf, Set};
 use crate::services::stack::StackServiceIf;
-use crate::services::PageInfo;
+use crate::services::auth::{AuthServiceIf, Session};
+use crate::services::{PageInfo, Paged};
 use crate::utils::ExtendType;
 use crate::services::groups::GroupsServiceIf;
 
 pub struct Query;
 
+/// Connection over the page already loaded by service, edge cursor is object offset
+fn paged_connection<T, U>(paged: Paged<T>) -> Connection<usize, U, PageInfo, EmptyFields>
+where
+    T: Into<U>,
+{
+    let offset = paged.page_info.offset as usize;
+    let total = paged.page_info.total.unwrap_or(0) as usize;
+    let has_next_page = offset + paged.objects.len() < total;
+
+    let mut connection =
+        Connection::with_additional_fields(offset > 0, has_next_page, paged.page_info);
+    connection.append(
+        paged
+            .objects
+            .into_iter()
+            .enumerate()
+            .map(|(i, object)| Edge::new(offset + i, object.into())),
+    );
+    connection
+}
+
 pub struct Sets;
 
 #[Object]
 impl Sets {
-    pub async fn recents(&self, ctx: &Context<'_>, access: String) -> Result<Vec<UserSet>> {
+    #[graphql(guard(AuthGuard()))]
+    pub async fn recents(&self, ctx: &Context<'_>) -> Result<Vec<UserSet>> {
         let ctr: &Container = ctx.data_unchecked::<Container>();
-        let auth: &dyn AuthServiceIf = ctr.resolve_ref();
-        let user = auth.validate_access(&access, Utc::now()).await?;
+        let user = current_user(ctx);
 
         let groups: &dyn GroupsServiceIf = ctr.resolve_ref();
 
         Ok(groups.recent_sets(user).await)
     }
+
+    /// all sets created by user, sorted by name ascending by default
+    #[graphql(guard(AuthGuard()))]
+    pub async fn all(
+        &self,
+        ctx: &Context<'_>,
+        paging: Option<Paging>,
+        sort_by: Option<SetsSortBy>,
+        name_contains: Option<String>,
+    ) -> Result<Connection<usize, UserSet, PageInfo, EmptyFields>> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let groups: &dyn GroupsServiceIf = ctr.resolve_ref();
+        let sets = groups
+            .list_sets(
+                user,
+                paging,
+                sort_by.unwrap_or(SetsSortBy::NameAsc),
+                name_contains,
+            )
+            .await
+            .extend_type()?;
+
+        Ok(paged_connection(sets))
+    }
 }
 
 #[Object]
@@ -45,7 +95,7 @@ impl Query {
         Sets
     }
 
-    // pub async fn recent_sets(&self, ctx: &Context<'_>, access: String) -> Result<Vec<Set>> {
+    // pub async fn recent_sets(&self, ctx: &Context<'_>) -> Result<Vec<Set>> {
     //     let ctr: &Container = ctx.data_unchecked::<Container>();
     //     let auth: &dyn AuthServiceIf = ctr.resolve_ref();
     //     let user = auth.validate_access(&access, Utc::now()).await?;
@@ -57,53 +107,129 @@ impl Query {
     //         name: "123".to_string(),
     //     }])
     // }
-    //
-    // pub async fn list_groups(
-    //     &self,
-    //     ctx: &Context<'_>,
-    //     access: String,
-    //     group_set: Option<String>,
-    //     paging: Option<Paging>,
-    // ) -> Result<Connection<usize, UserGroup, PageInfo, EmptyFields>> {
-    //     let ctr: &Container = ctx.data_unchecked::<Container>();
-    //     let groups: &dyn GroupsServiceIf = ctr.resolve_ref();
-    //     let auth: &dyn AuthServiceIf = ctr.resolve_ref();
-    //     let user = auth.validate_access(&access, Utc::now()).await?;
-    //
-    //     query(None, None, None, None, |_, _, _, _| async move {
-    //         let sl = groups
-    //             .list_groups(user, group_set.into(), paging)
-    //             .await
-    //             .extend_type()?;
-    //
-    //         let mut connection = Connection::with_additional_fields(false, false, sl.page_info);
-    //         connection.append(
-    //             sl.objects
-    //                 .into_iter()
-    //                 .map(|item| Edge::new(item.order as usize, item)),
-    //         );
-    //         Ok(connection)
-    //     })
-    //     .await
-    // }
 
-    // pub async fn my_stack(&self, ctx: &Context<'_>, access: String) -> Result<Vec<StackItem>> {
-    //     let ctr: &Container = ctx.data_unchecked::<Container>();
-    //     let auth: &dyn AuthServiceIf = ctr.resolve_ref();
-    //     let user = auth
-    //         .validate_access(&access, &Utc::now())
-    //         .await
-    //         .extend_type()?;
-    //
-    //     let stack_service: &dyn StackServiceIf = ctx.data_unchecked::<Container>().resolve_ref();
-    //     Ok(stack_service
-    //         .my_stack(user)
-    //         .await
-    //         .into_iter()
-    //         .map(|i| i.into())
-    //         .collect())
-    // }
-    //
+    #[graphql(guard(AuthGuard()))]
+    pub async fn list_groups(
+        &self,
+        ctx: &Context<'_>,
+        group_set: Option<String>,
+        paging: Option<Paging>,
+    ) -> Result<Connection<usize, UserGroup, PageInfo, EmptyFields>> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let groups: &dyn GroupsServiceIf = ctr.resolve_ref();
+        let user = current_user(ctx);
+
+        let sl = groups
+            .list_groups(user, group_set.into(), paging, Utc::now())
+            .await
+            .extend_type()?;
+
+        Ok(paged_connection(sl))
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn my_stack(&self, ctx: &Context<'_>) -> Result<Vec<StackItem>> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        Ok(stack_service
+            .my_stack(user)
+            .await
+            .into_iter()
+            .map(|i| i.into())
+            .collect())
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn stack_peek(&self, ctx: &Context<'_>) -> Result<Option<StackItem>> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        Ok(stack_service.stack_peek(user).await.map(|i| i.into()))
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn search_stack(
+        &self,
+        ctx: &Context<'_>,
+        query: String,
+        paging: Option<Paging>,
+    ) -> Result<Connection<usize, SearchHit, PageInfo, EmptyFields>> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        let found = stack_service
+            .search_my_stack(user, query, paging.into())
+            .await
+            .extend_type()?;
+
+        Ok(paged_connection(found))
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn group_items(
+        &self,
+        ctx: &Context<'_>,
+        group_id: Id,
+        paging: Option<Paging>,
+    ) -> Result<Connection<usize, StackItem, PageInfo, EmptyFields>> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        let items = stack_service
+            .group_items(user, group_id, paging.into())
+            .await
+            .extend_type()?;
+
+        Ok(paged_connection(items))
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn trash(&self, ctx: &Context<'_>) -> Result<Vec<StackItem>> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        Ok(stack_service
+            .trash(user)
+            .await
+            .into_iter()
+            .map(|i| i.into())
+            .collect())
+    }
+
+    #[graphql(guard(AuthGuard()))]
+    pub async fn block_history(
+        &self,
+        ctx: &Context<'_>,
+        block_id: Id,
+    ) -> Result<Vec<BlockVersion>> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_service: &dyn StackServiceIf = ctr.resolve_ref();
+        stack_service
+            .block_history(user, block_id)
+            .await
+            .map(|versions| versions.into_iter().map(|v| v.into()).collect())
+            .extend_type()
+    }
+
+    /// active sessions of user, the one request is made from is marked as current
+    #[graphql(guard(AuthGuard()))]
+    pub async fn my_sessions(&self, ctx: &Context<'_>) -> Result<Vec<Session>> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+        let access = ctx.data_unchecked::<CurrentAccess>();
+
+        let auth: &dyn AuthServiceIf = ctr.resolve_ref();
+        Ok(auth.sessions(&user, &access.0, Utc::now()).await)
+    }
+
     // pub async fn my_groups(
     //     &self,
     //     ctx: &Context<'_>,
@@ -1,15 +1,23 @@
-use async_graphql::SimpleObject;
+use async_graphql::{Enum, InputObject, SimpleObject};
+use chrono::{DateTime, Utc};
 use serde::Serialize;
 
+use crate::channels::stack::StackItemEvent;
+use crate::repos::marks::MarkKind;
 use crate::repos::Id;
 use crate::services::stack::{
-    Block as ServiceBlock, Mark as ServiceMark, StackItem as ServiceStackItem,
+    Block as ServiceBlock, BlockVersion as ServiceBlockVersion, Highlight as ServiceHighlight,
+    Mark as ServiceMark, SearchHit as ServiceSearchHit, StackItem as ServiceStackItem,
 };
 
 #[derive(Debug, Clone, SimpleObject)]
 pub struct StackItem {
     pub id: Id,
     pub blocks: Vec<Block>,
+    pub version: i32,
+    /// groups the item is filed into
+    pub groups_ids: Vec<Id>,
+    pub created_at: DateTime<Utc>,
 }
 
 impl From<ServiceStackItem> for StackItem {
@@ -17,6 +25,84 @@ impl From<ServiceStackItem> for StackItem {
         StackItem {
             id: item.id,
             blocks: item.blocks.into_iter().map(|b| b.into()).collect(),
+            version: item.version,
+            groups_ids: item.groups_ids,
+            created_at: item.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct SearchHit {
+    pub stack_item: StackItem,
+    pub highlights: Vec<Highlight>,
+}
+
+impl From<ServiceSearchHit> for SearchHit {
+    fn from(hit: ServiceSearchHit) -> Self {
+        SearchHit {
+            stack_item: hit.stack_item.into(),
+            highlights: hit.highlights.into_iter().map(|h| h.into()).collect(),
+        }
+    }
+}
+
+/// Part of block text matched by search.
+/// Has the shape of `Mark` to be rendered as a highlight mark
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct Highlight {
+    pub block_id: Id,
+    /// offset in UTF-16 code units
+    pub from: i32,
+    /// offset in UTF-16 code units, exclusive
+    pub to: i32,
+    pub kind: MarkKind,
+}
+
+impl From<ServiceHighlight> for Highlight {
+    fn from(highlight: ServiceHighlight) -> Self {
+        Highlight {
+            block_id: highlight.block_id,
+            from: highlight.from,
+            to: highlight.to,
+            kind: MarkKind::Highlight,
+        }
+    }
+}
+
+#[derive(Enum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StackItemChangeType {
+    Created,
+    Updated,
+    Deleted,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct StackItemChange {
+    pub change_type: StackItemChangeType,
+    pub id: Id,
+    /// not set for deleted item
+    pub stack_item: Option<StackItem>,
+}
+
+impl From<StackItemEvent> for StackItemChange {
+    fn from(event: StackItemEvent) -> Self {
+        match event {
+            StackItemEvent::Created(item) => StackItemChange {
+                change_type: StackItemChangeType::Created,
+                id: item.id.clone(),
+                stack_item: Some(item.into()),
+            },
+            StackItemEvent::Updated(item) => StackItemChange {
+                change_type: StackItemChangeType::Updated,
+                id: item.id.clone(),
+                stack_item: Some(item.into()),
+            },
+            StackItemEvent::Deleted(id) => StackItemChange {
+                change_type: StackItemChangeType::Deleted,
+                id,
+                stack_item: None,
+            },
         }
     }
 }
@@ -41,8 +127,13 @@ impl From<ServiceBlock> for Block {
 #[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
 pub struct Mark {
     pub id: Id,
+    /// offset in UTF-16 code units
     pub from: i32,
+    /// offset in UTF-16 code units, exclusive
     pub to: i32,
+    pub kind: MarkKind,
+    pub href: Option<String>,
+    pub color: Option<String>,
 }
 
 impl From<ServiceMark> for Mark {
@@ -51,57 +142,118 @@ impl From<ServiceMark> for Mark {
             id: mark.id,
             from: mark.from,
             to: mark.to,
+            kind: mark.kind,
+            href: mark.href,
+            color: mark.color,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct BlockVersion {
+    pub block_id: Id,
+    pub version: i32,
+    pub text: String,
+    pub marks: Vec<Mark>,
+}
+
+impl From<ServiceBlockVersion> for BlockVersion {
+    fn from(version: ServiceBlockVersion) -> Self {
+        BlockVersion {
+            block_id: version.block_id,
+            version: version.version,
+            text: version.text,
+            marks: version.marks.into_iter().map(|m| m.into()).collect(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, InputObject)]
 pub struct NewStackItem {
     pub blocks: Vec<NewBlock>,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, InputObject)]
 pub struct NewBlock {
     pub text: String,
     pub marks: Vec<NewMark>,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, InputObject)]
 pub struct NewMark {
+    /// offset in UTF-16 code units
     pub from: i32,
+    /// offset in UTF-16 code units, exclusive
     pub to: i32,
+    pub kind: MarkKind,
+    pub href: Option<String>,
+    pub color: Option<String>,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, InputObject)]
 pub struct StackItemChangeSet {
     pub stack_id: Id,
+    /// version of stack item changes were made against
+    pub version: i32,
     pub inserted: Option<InsertChangeSet>,
     pub removed: Vec<Id>,
     pub updated: Vec<UpdateBlock>,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, InputObject)]
 pub struct InsertChangeSet {
     pub insert_after_id: Option<Id>,
     pub blocks: Vec<InsertBlock>,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, InputObject)]
 pub struct InsertBlock {
     pub text: String,
     pub marks: Vec<ChangeMark>,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, InputObject)]
 pub struct UpdateBlock {
     pub id: Id,
     pub text: String,
-    pub marks: Vec<ChangeMark>,
+    /// if not passed existing block marks are moved along with the changed text
+    pub marks: Option<Vec<ChangeMark>>,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, InputObject)]
 pub struct ChangeMark {
     pub id: Option<Id>,
+    /// offset in UTF-16 code units
     pub from: i32,
+    /// offset in UTF-16 code units, exclusive
     pub to: i32,
+    pub kind: MarkKind,
+    pub href: Option<String>,
+    pub color: Option<String>,
+}
+
+impl From<NewMark> for ChangeMark {
+    fn from(mark: NewMark) -> Self {
+        ChangeMark {
+            id: None,
+            from: mark.from,
+            to: mark.to,
+            kind: mark.kind,
+            href: mark.href,
+            color: mark.color,
+        }
+    }
+}
+
+impl From<ServiceMark> for ChangeMark {
+    fn from(mark: ServiceMark) -> Self {
+        ChangeMark {
+            id: Some(mark.id),
+            from: mark.from,
+            to: mark.to,
+            kind: mark.kind,
+            href: mark.href,
+            color: mark.color,
+        }
+    }
 }
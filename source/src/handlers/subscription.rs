@@ -1,18 +1,48 @@
-use std::time::Duration;
-
-use actix_rt::time::Instant;
-use async_graphql::Subscription;
+use async_graphql::guard::Guard;
+use async_graphql::{Context, Result, Subscription};
 use futures::{Stream, StreamExt};
+use shaku::HasComponent;
+
+use crate::channels::groups::{GroupsChannelIf, SetKey};
+use crate::channels::stack::StackChannelIf;
+use crate::container::Container;
+use crate::handlers::auth::{current_user, AuthGuard};
+use crate::handlers::groups::GroupChange;
+use crate::handlers::stack::StackItemChange;
 
 pub struct Subscription;
 
 #[Subscription]
 impl Subscription {
-    async fn interval(&self, #[graphql(default = 1)] _n: i32) -> impl Stream<Item = i32> {
-        let mut a = 0;
-        actix::clock::interval_at(Instant::now(), Duration::from_secs(1)).map(move |_| {
-            a += 1;
-            a
-        })
+    /// changes of user stack made from any of user devices
+    #[graphql(guard(AuthGuard()))]
+    async fn my_stack_changes(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<impl Stream<Item = StackItemChange>> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let stack_channel: &dyn StackChannelIf = ctr.resolve_ref();
+        Ok(stack_channel.subscribe(&user.id).map(|e| e.into()))
+    }
+
+    /// changes of groups in user set, the default one if `set_name` is not passed
+    #[graphql(guard(AuthGuard()))]
+    async fn my_set_changes(
+        &self,
+        ctx: &Context<'_>,
+        set_name: Option<String>,
+    ) -> Result<impl Stream<Item = GroupChange>> {
+        let ctr: &Container = ctx.data_unchecked::<Container>();
+        let user = current_user(ctx);
+
+        let groups_channel: &dyn GroupsChannelIf = ctr.resolve_ref();
+        Ok(groups_channel
+            .subscribe(&SetKey {
+                user_id: user.id,
+                set_name,
+            })
+            .map(|e| e.into()))
     }
 }
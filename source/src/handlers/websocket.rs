@@ -0,0 +1,234 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix::{
+    Actor, ActorContext, ActorFuture, ActorStream, AsyncContext, ContextFutureSpawner,
+    StreamHandler, WrapFuture, WrapStream,
+};
+use actix_http::ws::Item;
+use actix_web_actors::ws::{CloseCode, CloseReason, Message, ProtocolError, WebsocketContext};
+use async_graphql::http::WebSocket;
+use async_graphql::Data;
+use chrono::{DateTime, Utc};
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use serde_json::json;
+
+use crate::channels::sessions::{SessionEvent, SessionsChannelIf};
+use crate::errors::AppError;
+use crate::handlers::auth::init_token;
+use crate::handlers::Root;
+use crate::repos::users::User;
+use crate::services::auth::AuthServiceIf;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+///
+/// Вебсокет подписок как `WSSubscription` из async-graphql-actix-web, только с авторизацией.
+/// Токен из `connection_init` проверяется до `connection_ack`, с невалидным токеном
+/// соединение отклоняется. Соединение закрывается когда доступ истекает
+/// или сессия пользователя отозвана, вместе с ним заканчиваются все подписки
+///
+pub struct AuthWSSubscription {
+    schema: Root,
+    auth: Arc<dyn AuthServiceIf>,
+    sessions_channel: Arc<dyn SessionsChannelIf>,
+    access: String,
+    last_heartbeat: Instant,
+    /// появляется после проверки токена, до этого ждём только `connection_init`
+    messages: Option<UnboundedSender<Vec<u8>>>,
+    continuation: Vec<u8>,
+}
+
+impl AuthWSSubscription {
+    pub fn new(
+        schema: Root,
+        auth: Arc<dyn AuthServiceIf>,
+        sessions_channel: Arc<dyn SessionsChannelIf>,
+    ) -> Self {
+        Self {
+            schema,
+            auth,
+            sessions_channel,
+            access: String::new(),
+            last_heartbeat: Instant::now(),
+            messages: None,
+            continuation: Vec::new(),
+        }
+    }
+
+    fn send_heartbeats(&self, ctx: &mut WebsocketContext<Self>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.last_heartbeat) > CLIENT_TIMEOUT {
+                ctx.stop();
+            }
+            ctx.ping(b"");
+        });
+    }
+
+    fn receive(&mut self, message: Vec<u8>, ctx: &mut WebsocketContext<Self>) {
+        match &self.messages {
+            Some(sender) => {
+                if sender.unbounded_send(message).is_err() {
+                    ctx.stop();
+                }
+            }
+            None => self.authenticate(message, ctx),
+        }
+    }
+
+    ///
+    /// Первым сообщением должен быть `connection_init` с токеном, пока токен проверяется
+    /// остальные сообщения не обрабатываются
+    ///
+    fn authenticate(&mut self, init: Vec<u8>, ctx: &mut WebsocketContext<Self>) {
+        let access = serde_json::from_slice::<serde_json::Value>(&init)
+            .ok()
+            .filter(|m| m["type"] == "connection_init")
+            .and_then(|m| init_token(&m["payload"]));
+        let access = match access {
+            Some(access) => access,
+            None => return self.reject(AppError::unauthorized(), ctx),
+        };
+        self.access = access.clone();
+
+        let auth = self.auth.clone();
+        async move { auth.validate_access_with_expiry(&access, Utc::now()).await }
+            .into_actor(self)
+            .map(|res, act, ctx| match res {
+                Ok((user, expires_at)) => act.authenticated(user, expires_at, init, ctx),
+                Err(e) => act.reject(e, ctx),
+            })
+            .wait(ctx);
+    }
+
+    fn authenticated(
+        &mut self,
+        user: User,
+        expires_at: DateTime<Utc>,
+        init: Vec<u8>,
+        ctx: &mut WebsocketContext<Self>,
+    ) {
+        ctx.add_stream(self.sessions_channel.subscribe(&user.id));
+        let left = (expires_at - Utc::now()).to_std().unwrap_or_default();
+        ctx.run_later(left, |act, ctx| act.close(AppError::access_expire(), ctx));
+
+        // у `connection_init` с токеном всегда есть payload, так что инициализатор вызовется
+        let initializer = move |_| {
+            let mut data = Data::default();
+            data.insert(user);
+            Ok(data)
+        };
+        let (tx, rx) = unbounded();
+        WebSocket::with_data(self.schema.clone(), rx, Some(initializer))
+            .into_actor(self)
+            .map(|response, _act, ctx| {
+                ctx.text(response);
+            })
+            .finish()
+            .spawn(ctx);
+
+        let _ = tx.unbounded_send(init);
+        self.messages = Some(tx);
+    }
+
+    /// Reply to `connection_init` with `connection_error` and close the connection
+    fn reject(&mut self, err: AppError, ctx: &mut WebsocketContext<Self>) {
+        let error = json!({
+            "type": "connection_error",
+            "payload": {
+                "message": err.to_string(),
+                "extensions": {"type": err.get_type()},
+            },
+        });
+        ctx.text(error.to_string());
+        self.close(err, ctx);
+    }
+
+    fn close(&mut self, err: AppError, ctx: &mut WebsocketContext<Self>) {
+        ctx.close(Some(CloseReason {
+            code: CloseCode::Policy,
+            description: Some(err.to_string()),
+        }));
+        ctx.stop();
+    }
+}
+
+impl Actor for AuthWSSubscription {
+    type Context = WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.send_heartbeats(ctx);
+    }
+}
+
+impl StreamHandler<Result<Message, ProtocolError>> for AuthWSSubscription {
+    fn handle(&mut self, msg: Result<Message, ProtocolError>, ctx: &mut Self::Context) {
+        let msg = match msg {
+            Err(_) => {
+                ctx.stop();
+                return;
+            }
+            Ok(msg) => msg,
+        };
+
+        let message = match msg {
+            Message::Ping(msg) => {
+                self.last_heartbeat = Instant::now();
+                ctx.pong(&msg);
+                None
+            }
+            Message::Pong(_) => {
+                self.last_heartbeat = Instant::now();
+                None
+            }
+            Message::Continuation(item) => match item {
+                Item::FirstText(bytes) | Item::FirstBinary(bytes) => {
+                    self.continuation = bytes.to_vec();
+                    None
+                }
+                Item::Continue(bytes) => {
+                    self.continuation.extend_from_slice(&bytes);
+                    None
+                }
+                Item::Last(bytes) => {
+                    self.continuation.extend_from_slice(&bytes);
+                    Some(std::mem::take(&mut self.continuation))
+                }
+            },
+            Message::Text(s) => Some(s.into_bytes()),
+            Message::Binary(bytes) => Some(bytes.to_vec()),
+            Message::Close(_) => {
+                ctx.stop();
+                None
+            }
+            Message::Nop => None,
+        };
+
+        if let Some(message) = message {
+            self.receive(message, ctx);
+        }
+    }
+}
+
+///
+/// Какие-то сессии пользователя отозваны, если среди них эта - закрываем соединение
+///
+impl StreamHandler<SessionEvent> for AuthWSSubscription {
+    fn handle(&mut self, _: SessionEvent, ctx: &mut Self::Context) {
+        let auth = self.auth.clone();
+        let access = self.access.clone();
+
+        async move { auth.validate_access(&access, Utc::now()).await }
+            .into_actor(self)
+            .map(|res, act, ctx| {
+                if let Err(e) = res {
+                    act.close(e, ctx);
+                }
+            })
+            .spawn(ctx);
+    }
+
+    // канал сессий не заканчивается сам, а по умолчанию актор бы остановился
+    fn finished(&mut self, _: &mut Self::Context) {}
+}
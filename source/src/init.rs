@@ -4,8 +4,10 @@ use crate::db::{DBParameters, DB};
 use crate::logger::build_app_logger;
 use crate::logger::{AppLogger, AppLoggerParameters};
 use crate::mongo;
+use crate::repos::tokens::{TokensRepo, TokensRepoParameters};
 
 use crate::services::auth::{AuthService, AuthServiceParameters};
+use crate::services::groups::{GroupsService, GroupsServiceParameters};
 
 pub async fn init_app(config: &Config) -> Container {
     let mongo_client = mongo::client::build_client(
@@ -17,6 +19,8 @@ pub async fn init_app(config: &Config) -> Container {
     .await
     .expect("can not initialize mongo client");
 
+    // индексы токенов меняются миграцией, поэтому она раньше
+    mongo::migrations::migrate(mongo_client.database(&config.db_name), &config.token_secret).await;
     mongo::indexes::create_indexes(mongo_client.database(&config.db_name)).await;
 
     let container: Container = Container::builder()
@@ -30,6 +34,12 @@ pub async fn init_app(config: &Config) -> Container {
             access_token_lifetime: config.access_token_lifetime,
             refresh_token_lifetime: config.refresh_token_lifetime,
         })
+        .with_component_parameters::<TokensRepo>(TokensRepoParameters {
+            token_secret: config.token_secret.clone(),
+        })
+        .with_component_parameters::<GroupsService>(GroupsServiceParameters {
+            recent_sets_max: config.recent_sets_max,
+        })
         .with_component_parameters::<AppLogger>(AppLoggerParameters {
             logger: build_app_logger(&config),
         })
@@ -1,10 +1,13 @@
 use std::io;
+use std::sync::Arc;
 
 use actix_cors::Cors;
 use actix_web::{guard, http::header, http::Method, middleware, web, App, HttpServer};
 use async_graphql::Schema;
+use shaku::HasComponent;
 use url::Url;
 
+use motor_back::channels::sessions::SessionsChannelIf;
 use motor_back::config::Config;
 use motor_back::container::Container;
 use motor_back::handlers::mutation::Mutation;
@@ -13,12 +16,15 @@ use motor_back::handlers::{
     subscription::Subscription,
 };
 use motor_back::init::init_app;
+use motor_back::services::auth::AuthServiceIf;
 
 #[actix_rt::main]
 async fn main() -> Result<(), io::Error> {
     let config = Config::load();
 
     let container: Container = init_app(&config).await;
+    let auth: Arc<dyn AuthServiceIf> = container.resolve();
+    let sessions_channel: Arc<dyn SessionsChannelIf> = container.resolve();
 
     let bind_addr = format!("{}:{}", &config.host, &config.port);
     let self_host = format!("{}://{}:{}", &config.proto, &config.host, &config.port);
@@ -56,6 +62,8 @@ async fn main() -> Result<(), io::Error> {
             .wrap(cors)
             .wrap(middleware::Logger::default())
             .data(schema.clone())
+            .data(auth.clone())
+            .data(sessions_channel.clone())
             .service(web::resource("/").guard(guard::Post()).to(graphql))
             .service(
                 web::resource("/")
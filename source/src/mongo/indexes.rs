@@ -17,19 +17,92 @@ lazy_static! {
             doc! {
                 "createIndexes": crate::repos::tokens::COLLECTION,
                 "indexes": [{
-                    "key": {"access": 1},
-                    "name": "unique_access_token",
+                    "key": {"access_hash": 1},
+                    "name": "unique_access_token_hash",
                     "unique": true
                 }]
             },
             doc! {
                 "createIndexes": crate::repos::tokens::COLLECTION,
                 "indexes": [{
-                    "key": {"refresh": 1},
-                    "name": "unique_refresh_token",
+                    "key": {"refresh_hash": 1},
+                    "name": "unique_refresh_token_hash",
                     "unique": true
                 }]
             },
+            doc! {
+                "createIndexes": crate::repos::tokens::COLLECTION,
+                "indexes": [{
+                    "key": {"family": 1},
+                    "name": "token_family"
+                }, {
+                    // pairs are deleted when refresh token expires
+                    "key": {"expires_at": 1},
+                    "name": "token_expiry",
+                    "expireAfterSeconds": 0
+                }]
+            },
+            doc! {
+                "createIndexes": crate::repos::blocks::COLLECTION,
+                "indexes": [{
+                    "key": {"text": "text"},
+                    "name": "text_search",
+                    // blocks are written in different languages,
+                    // so words are matched as they are without stemming
+                    "default_language": "none"
+                }]
+            },
+            doc! {
+                "createIndexes": crate::repos::stack::COLLECTION,
+                "indexes": [{
+                    "key": {"user_id": 1, "pushed_at": -1, "_id": -1},
+                    "name": "stack_lifo"
+                }]
+            },
+            doc! {
+                "createIndexes": crate::repos::group_sets::COLLECTION,
+                "indexes": [{
+                    "key": {"user_id": 1, "set_name": 1, "order_key": 1},
+                    "name": "set_order"
+                }]
+            },
+            doc! {
+                "createIndexes": crate::repos::default_group_sets::COLLECTION,
+                "indexes": [{
+                    "key": {"user_id": 1, "order_key": 1},
+                    "name": "set_order"
+                }]
+            },
+            doc! {
+                "createIndexes": crate::repos::group_items::COLLECTION,
+                "indexes": [{
+                    "key": {"group_id": 1, "stack_id": 1},
+                    "name": "unique_group_item",
+                    "unique": true
+                }, {
+                    "key": {"group_id": 1, "order_key": 1},
+                    "name": "group_order"
+                }]
+            },
+            doc! {
+                "createIndexes": crate::repos::sets::COLLECTION,
+                "indexes": [{
+                    "key": {"creator_id": 1, "name": 1},
+                    "name": "creator_sets"
+                }]
+            },
+            doc! {
+                "createIndexes": crate::repos::recent_sets::COLLECTION,
+                "indexes": [{
+                    // not unique, recents stored before `last_used_at` was added
+                    // may hold the same set twice
+                    "key": {"user_id": 1, "set_id": 1},
+                    "name": "user_recent_set"
+                }, {
+                    "key": {"user_id": 1, "last_used_at": -1, "_id": -1},
+                    "name": "user_recency"
+                }]
+            },
         ]
     };
 }
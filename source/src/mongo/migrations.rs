@@ -0,0 +1,141 @@
+use bson::document::Document;
+use bson::Bson;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use mongodb::Database;
+
+use crate::repos::default_group_sets::COLLECTION as DEFAULT_GROUP_SETS;
+use crate::repos::group_sets::COLLECTION as GROUP_SETS;
+use crate::repos::tokens::{hash_token, COLLECTION as TOKENS};
+use crate::services::ordering::spread_keys;
+
+///
+/// Приводит данные старых версий к текущей схеме, запускается до создания индексов.
+/// Миграции можно запускать сколько угодно раз, уже мигрированные данные не трогаются
+///
+pub async fn migrate(db: Database, token_secret: &str) {
+    hash_plain_tokens(&db, token_secret).await;
+    token_expiry_dates(&db).await;
+    order_keys_from_orders(&db, GROUP_SETS, &["user_id", "set_name"]).await;
+    order_keys_from_orders(&db, DEFAULT_GROUP_SETS, &["user_id"]).await;
+}
+
+async fn find_all(db: &Database, collection: &str, criteria: Document) -> Vec<Document> {
+    db.collection(collection)
+        .find(criteria, None)
+        .await
+        .unwrap()
+        .map(|x| x.unwrap())
+        .collect()
+        .await
+}
+
+///
+/// Раньше токены хранились как есть в `access` и `refresh` с уникальными индексами на них.
+/// Теперь храним `access_hash` и `refresh_hash`, старые пары хешируем
+/// чтобы залогиненные юзеры не вылетели
+///
+async fn hash_plain_tokens(db: &Database, token_secret: &str) {
+    for index in &["unique_access_token", "unique_refresh_token"] {
+        // индекса нет если база новая или миграция уже была
+        let _ = db
+            .run_command(doc! {"dropIndexes": TOKENS, "index": *index}, None)
+            .await;
+    }
+
+    let tokens = db.collection(TOKENS);
+    let plain = find_all(db, TOKENS, doc! {"access": {"$exists": true}}).await;
+
+    for pair in plain {
+        let access = pair.get_str("access").unwrap_or_default();
+        let refresh = pair.get_str("refresh").unwrap_or_default();
+
+        tokens
+            .update_one(
+                doc! {"_id": pair.get("_id").unwrap().clone()},
+                doc! {
+                    "$set": {
+                        "access_hash": hash_token(token_secret, access),
+                        "refresh_hash": hash_token(token_secret, refresh),
+                    },
+                    "$unset": {"access": "", "refresh": ""},
+                },
+                None,
+            )
+            .await
+            .unwrap();
+    }
+}
+
+///
+/// Пары токенов удаляются TTL индексом по `expires_at`, у старых пар его нет.
+/// Берём его из `refresh_lifetime`, который хранится строкой
+///
+async fn token_expiry_dates(db: &Database) {
+    let tokens = db.collection(TOKENS);
+    let without_expiry = find_all(db, TOKENS, doc! {"expires_at": {"$exists": false}}).await;
+
+    for pair in without_expiry {
+        let expires_at = pair
+            .get_str("refresh_lifetime")
+            .ok()
+            .and_then(|l| DateTime::parse_from_rfc3339(l).ok())
+            .map(|l| l.with_timezone(&Utc))
+            // без срока пара всё равно не валидна
+            .unwrap_or(Utc::now());
+
+        tokens
+            .update_one(
+                doc! {"_id": pair.get("_id").unwrap().clone()},
+                doc! {"$set": {"expires_at": expires_at}},
+                None,
+            )
+            .await
+            .unwrap();
+    }
+}
+
+///
+/// Раньше группы в сетах были упорядочены числовым `order`, теперь строковым `order_key`.
+/// Сеты где есть группы без ключа получают ключи `spread_keys` в порядке старого `order`.
+/// `set_fields` это поля по которым группы собраны в сет
+///
+async fn order_keys_from_orders(db: &Database, collection: &str, set_fields: &[&str]) {
+    let legacy = find_all(db, collection, doc! {"order_key": {"$exists": false}}).await;
+
+    let mut sets: Vec<Document> = vec![];
+    for item in legacy {
+        let set: Document = set_fields
+            .iter()
+            .map(|f| (f.to_string(), item.get(f).cloned().unwrap_or(Bson::Null)))
+            .collect();
+        if !sets.contains(&set) {
+            sets.push(set);
+        }
+    }
+
+    for set in sets {
+        let mut items = find_all(db, collection, set).await;
+        // группы с ключами если и есть, то добавлены после старых
+        items.sort_by_key(|i| {
+            (
+                i.get_i32("order").unwrap_or(i32::MAX),
+                i.get_str("order_key").unwrap_or_default().to_string(),
+                i.get_object_id("_id")
+                    .map(|id| id.to_hex())
+                    .unwrap_or_default(),
+            )
+        });
+
+        for (item, key) in items.iter().zip(spread_keys(items.len())) {
+            db.collection(collection)
+                .update_one(
+                    doc! {"_id": item.get("_id").unwrap().clone()},
+                    doc! {"$set": {"order_key": key}, "$unset": {"order": ""}},
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+    }
+}
@@ -1,2 +1,3 @@
 pub mod client;
-pub mod indexes;
\ No newline at end of file
+pub mod indexes;
+pub mod migrations;
\ No newline at end of file
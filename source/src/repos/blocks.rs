@@ -1,6 +1,8 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use futures::StreamExt;
+use mongodb::options::FindOptions;
 use serde::{Deserialize, Serialize};
 use shaku::{Component, Interface};
 use slog::Logger;
@@ -9,10 +11,14 @@ use proc_macro::HasLogger;
 
 use crate::db::DBIf;
 use crate::logger::AppLoggerIf;
+use bson::oid::ObjectId;
+
 use crate::repos::db::{
-    find_many_by_ids, find_one_by_id, insert_one_into, link_external_ids, update_one_by_id,
+    delete_many_by, find_many_by_ids, find_one_by_id, insert_one_into, link_external_ids,
+    update_one_by_id,
 };
 use crate::repos::Id;
+use crate::utils::{deserialize_bson, IntoAppErr, LogErrWith};
 
 pub const COLLECTION: &str = "blocks";
 
@@ -26,6 +32,8 @@ pub struct Block {
     pub marks_ids: Vec<Id>,
     pub current_version: i32,
     pub initial_version: i32,
+    #[serde(default)]
+    pub removed: bool,
 }
 
 #[derive(Serialize, Debug)]
@@ -44,12 +52,22 @@ pub trait BlocksRepoIf: Interface {
 
     async fn mark_removed(&self, id: &Id) -> bool;
 
-    /// returns (old_block, new_block)
-    async fn update(&self, old: &Block, new_text: &str) -> (Block, Block);
+    async fn unmark_removed(&self, id: &Id) -> bool;
+
+    /// overwrites order, text, marks and current version of the block with the given id
+    async fn update(&self, block: &Block) -> bool;
+
+    async fn delete_many(&self, ids: Vec<&Id>);
 
     async fn link_marks(&self, block: &Block, marks_ids: &Vec<Id>) -> Block;
 
+    async fn find(&self, id: &Id) -> Option<Block>;
+
     async fn find_by_ids(&self, ids: Vec<&Id>) -> Vec<Block>;
+
+    /// Full text search over not removed blocks of the given stack items.
+    /// Returns ids of stack items having matched blocks, best matches first
+    async fn search_stacks_ids(&self, stacks_ids: &Vec<Id>, query: &str) -> Vec<Id>;
 }
 
 #[shaku(interface = BlocksRepoIf)]
@@ -76,6 +94,7 @@ impl BlocksRepoIf for BlocksRepo {
             marks_ids: vec![],
             current_version: insert_block.current_version,
             initial_version: insert_block.initial_version,
+            removed: false,
         }
     }
 
@@ -83,35 +102,30 @@ impl BlocksRepoIf for BlocksRepo {
         update_one_by_id(&self.db.get(), COLLECTION, id, doc! {"removed": true}).await
     }
 
-    async fn update(&self, _old: &Block, _new_text: &str) -> (Block, Block) {
-        unimplemented!()
-        // let old = old.clone();
-        //
-        // let inserted_id = insert_one_into(
-        //     &self.db.get(),
-        //     COLLECTION,
-        //     &InsertBlock {
-        //         stack_id: old.stack_id,
-        //         text: old.text,
-        //         marks_ids: old.marks_ids,
-        //         current_version: 0,
-        //         initial_version: 0,
-        //     },
-        //     self.logger(),
-        // )
-        // .await;
-        //
-        // set_by_id(&self.db.get(), COLLECTION, &old.id, doc! { "text": new_text }).await;
-        // // inc_version(&self.db.get(), COLLECTION, &old.id).await;
-        //
-        // let old_block = find_one_by_id(&self.db.get(), COLLECTION, &inserted_id, self.logger())
-        //     .await
-        //     .unwrap();
-        // let new_block = find_one_by_id(&self.db.get(), COLLECTION, &old.id, self.logger())
-        //     .await
-        //     .unwrap();
-        //
-        // (old_block, new_block)
+    async fn unmark_removed(&self, id: &Id) -> bool {
+        update_one_by_id(&self.db.get(), COLLECTION, id, doc! {"removed": false}).await
+    }
+
+    async fn update(&self, block: &Block) -> bool {
+        let marks_ids: Vec<ObjectId> = block.marks_ids.iter().map(|x| x.oid()).collect();
+
+        update_one_by_id(
+            &self.db.get(),
+            COLLECTION,
+            &block.id,
+            doc! {
+                "order": block.order,
+                "text": &block.text,
+                "marks_ids": marks_ids,
+                "current_version": block.current_version,
+            },
+        )
+        .await
+    }
+
+    async fn delete_many(&self, ids: Vec<&Id>) {
+        let ids: Vec<ObjectId> = ids.iter().map(|x| x.oid()).collect();
+        delete_many_by(&self.db.get(), COLLECTION, doc! {"_id": {"$in": ids}}).await;
     }
 
     async fn link_marks(&self, block: &Block, marks_ids: &Vec<Id>) -> Block {
@@ -129,7 +143,54 @@ impl BlocksRepoIf for BlocksRepo {
             .unwrap()
     }
 
+    async fn find(&self, id: &Id) -> Option<Block> {
+        find_one_by_id(&self.db.get(), COLLECTION, id, self.logger()).await
+    }
+
     async fn find_by_ids(&self, ids: Vec<&Id>) -> Vec<Block> {
         find_many_by_ids(&self.db.get(), COLLECTION, ids, self.logger()).await
     }
+
+    async fn search_stacks_ids(&self, stacks_ids: &Vec<Id>, query: &str) -> Vec<Id> {
+        #[derive(Deserialize)]
+        struct FoundBlock {
+            stack_id: Id,
+        }
+
+        let stacks_ids: Vec<ObjectId> = stacks_ids.iter().map(|x| x.oid()).collect();
+        let score = doc! {"$meta": "textScore"};
+
+        let found: Vec<FoundBlock> = self
+            .db
+            .get()
+            .collection(COLLECTION)
+            .find(
+                Some(doc! {
+                    "$text": {"$search": query},
+                    "stack_id": {"$in": stacks_ids},
+                    "removed": {"$ne": true},
+                }),
+                Some(
+                    FindOptions::builder()
+                        .projection(doc! {"stack_id": 1, "score": score.clone()})
+                        .sort(doc! {"score": score})
+                        .build(),
+                ),
+            )
+            .await
+            .log_err_with(self.logger())
+            .into_app_err()
+            .unwrap()
+            .map(|x| deserialize_bson(&x.unwrap()))
+            .collect()
+            .await;
+
+        let mut ids: Vec<Id> = vec![];
+        for block in found {
+            if !ids.contains(&block.stack_id) {
+                ids.push(block.stack_id);
+            }
+        }
+        ids
+    }
 }
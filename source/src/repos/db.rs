@@ -1,7 +1,7 @@
 use bson::oid::ObjectId;
 use bson::Document;
 use futures::StreamExt;
-use mongodb::options::FindOptions;
+use mongodb::options::{FindOneOptions, FindOptions};
 use mongodb::Database;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -50,6 +50,28 @@ where
         .map(|x| deserialize_bson(&x))
 }
 
+pub(crate) async fn find_first_by<T>(
+    db: &Database,
+    collection: &str,
+    criteria: Document,
+    sort: Document,
+    logger: &Logger,
+) -> Option<T>
+where
+    T: DeserializeOwned,
+{
+    db.collection(collection)
+        .find_one(
+            Some(criteria),
+            Some(FindOneOptions::builder().sort(sort).build()),
+        )
+        .await
+        .log_err_with(logger)
+        .into_app_err()
+        .unwrap()
+        .map(|x| deserialize_bson(&x))
+}
+
 pub(crate) async fn find_many_by_ids<T>(
     db: &Database,
     collection: &str,
@@ -76,6 +98,7 @@ where
 pub(crate) struct PaginationOptions {
     pub offset: i64,
     pub limit: i64,
+    pub sort: Option<Document>,
 }
 
 pub(crate) async fn paged_find_many_by<T>(
@@ -95,6 +118,7 @@ where
                 FindOptions::builder()
                     .skip(pagination.offset as i64)
                     .limit(pagination.limit as i64)
+                    .sort(pagination.sort)
                     .build(),
             ),
         )
@@ -107,6 +131,20 @@ where
         .await
 }
 
+pub(crate) async fn count_by(
+    db: &Database,
+    collection: &str,
+    criteria: Document,
+    logger: &Logger,
+) -> i64 {
+    db.collection(collection)
+        .count_documents(criteria, None)
+        .await
+        .log_err_with(logger)
+        .into_app_err()
+        .unwrap()
+}
+
 pub(crate) async fn find_many_by<T>(
     db: &Database,
     collection: &str,
@@ -213,6 +251,21 @@ pub(crate) async fn update_one_by_id(
     update_result.modified_count > 0
 }
 
+pub(crate) async fn update_many_by(
+    db: &Database,
+    collection: &str,
+    criteria: Document,
+    set: Document,
+) -> bool {
+    let update_result = db
+        .collection(collection)
+        .update_many(criteria, doc! { "$set": set }, None)
+        .await
+        .unwrap();
+
+    update_result.modified_count > 0
+}
+
 pub(crate) async fn delete_one_by_id(db: &Database, collection: &str, id: &Id) -> bool {
     let id: ObjectId = id.clone().into();
 
@@ -1,8 +1,8 @@
 use crate::db::DBIf;
 use crate::logger::AppLoggerIf;
 use crate::repos::db::{
-    delete_many_by, find_many_by, find_one_by, find_one_by_id, insert_many_into, paged_find_many_by,
-    PaginationOptions,
+    count_by, delete_many_by, find_first_by, find_many_by, find_one_by, find_one_by_id,
+    insert_one_into, paged_find_many_by, update_many_by, update_one_by_id, PaginationOptions,
 };
 use crate::repos::Id;
 use async_trait::async_trait;
@@ -20,7 +20,7 @@ pub struct InsertDefaultGroupSetItem {
     pub user_id: Id,
     pub group_id: Id,
     pub group_name: String,
-    pub order: i32,
+    pub order_key: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -30,7 +30,8 @@ pub struct DefaultGroupSetItem {
     pub user_id: Id,
     pub group_id: Id,
     pub group_name: String,
-    pub order: i32,
+    /// see `services::ordering::key_between`
+    pub order_key: String,
 }
 
 #[async_trait]
@@ -39,10 +40,31 @@ pub trait DefaultGroupSetsRepoIf: Interface {
 
     async fn find_by_group_id(&self, group_id: &Id) -> Option<DefaultGroupSetItem>;
 
-    async fn insert_many(&self, items: Vec<&InsertDefaultGroupSetItem>);
+    async fn insert(&self, item: &InsertDefaultGroupSetItem) -> Id;
 
     async fn find_by_user_id(&self, user_id: &Id) -> Option<DefaultGroupSetItem>;
 
+    async fn find_by_user_id_and_group_name(
+        &self,
+        user_id: &Id,
+        group_name: &str,
+    ) -> Option<DefaultGroupSetItem>;
+
+    async fn get_by_user_id(&self, user_id: &Id) -> Vec<DefaultGroupSetItem>;
+
+    async fn count_by_user_id(&self, user_id: &Id) -> i64;
+
+    /// Первая группа сета с ключом больше `order_key`, или первая группа сета если `None`
+    async fn find_first_after(
+        &self,
+        user_id: &Id,
+        order_key: Option<&str>,
+    ) -> Option<DefaultGroupSetItem>;
+
+    async fn count_before(&self, user_id: &Id, order_key: &str) -> i64;
+
+    async fn set_order_key(&self, id: &Id, order_key: &str) -> bool;
+
     async fn get_paged_by_user_id(
         &self,
         user_id: &Id,
@@ -50,7 +72,10 @@ pub trait DefaultGroupSetsRepoIf: Interface {
         limit: i32,
     ) -> Vec<DefaultGroupSetItem>;
 
-    async fn remove_by_user_id(&self, user_id: &Id);
+    async fn remove_by_group_id(&self, group_id: &Id) -> bool;
+
+    /// Обновляет копию имени группы в дефолтном сете
+    async fn rename_group(&self, group_id: &Id, group_name: &str) -> bool;
 }
 
 #[shaku(interface = DefaultGroupSetsRepoIf)]
@@ -82,8 +107,8 @@ impl DefaultGroupSetsRepoIf for DefaultGroupSetsRepo {
         .await
     }
 
-    async fn insert_many(&self, items: Vec<&InsertDefaultGroupSetItem>) {
-        insert_many_into(&self.db.get(), COLLECTION, items, self.logger()).await;
+    async fn insert(&self, item: &InsertDefaultGroupSetItem) -> Id {
+        insert_one_into(&self.db.get(), COLLECTION, item, self.logger()).await
     }
 
     async fn find_by_user_id(&self, user_id: &Id) -> Option<DefaultGroupSetItem> {
@@ -98,6 +123,88 @@ impl DefaultGroupSetsRepoIf for DefaultGroupSetsRepo {
         .await
     }
 
+    async fn find_by_user_id_and_group_name(
+        &self,
+        user_id: &Id,
+        group_name: &str,
+    ) -> Option<DefaultGroupSetItem> {
+        let user_id: ObjectId = user_id.clone().into();
+
+        find_one_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"user_id": user_id, "group_name": group_name},
+            self.logger(),
+        )
+        .await
+    }
+
+    async fn get_by_user_id(&self, user_id: &Id) -> Vec<DefaultGroupSetItem> {
+        let user_id: ObjectId = user_id.clone().into();
+
+        let mut items: Vec<DefaultGroupSetItem> = find_many_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"user_id": user_id},
+            self.logger(),
+        )
+        .await;
+        items.sort_by(|a, b| a.order_key.cmp(&b.order_key));
+
+        items
+    }
+
+    async fn count_by_user_id(&self, user_id: &Id) -> i64 {
+        let user_id: ObjectId = user_id.clone().into();
+
+        count_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"user_id": user_id},
+            self.logger(),
+        )
+        .await
+    }
+
+    async fn find_first_after(
+        &self,
+        user_id: &Id,
+        order_key: Option<&str>,
+    ) -> Option<DefaultGroupSetItem> {
+        let user_id: ObjectId = user_id.clone().into();
+
+        find_first_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"user_id": user_id, "order_key": {"$gt": order_key.unwrap_or("")}},
+            doc! {"order_key": 1},
+            self.logger(),
+        )
+        .await
+    }
+
+    async fn count_before(&self, user_id: &Id, order_key: &str) -> i64 {
+        let user_id: ObjectId = user_id.clone().into();
+
+        count_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"user_id": user_id, "order_key": {"$lt": order_key}},
+            self.logger(),
+        )
+        .await
+    }
+
+    async fn set_order_key(&self, id: &Id, order_key: &str) -> bool {
+        update_one_by_id(
+            &self.db.get(),
+            COLLECTION,
+            id,
+            doc! {"order_key": order_key},
+        )
+        .await
+    }
+
     async fn get_paged_by_user_id(
         &self,
         user_id: &Id,
@@ -114,14 +221,27 @@ impl DefaultGroupSetsRepoIf for DefaultGroupSetsRepo {
             PaginationOptions {
                 offset: offset as i64,
                 limit: limit as i64,
+                sort: Some(doc! {"order_key": 1}),
             },
         )
         .await
     }
 
-    async fn remove_by_user_id(&self, user_id: &Id) {
-        let user_id: ObjectId = user_id.clone().into();
+    async fn remove_by_group_id(&self, group_id: &Id) -> bool {
+        let group_id: ObjectId = group_id.clone().into();
+
+        delete_many_by(&self.db.get(), COLLECTION, doc! {"group_id": group_id}).await
+    }
+
+    async fn rename_group(&self, group_id: &Id, group_name: &str) -> bool {
+        let group_id: ObjectId = group_id.clone().into();
 
-        delete_many_by(&self.db.get(), COLLECTION, doc! {"user_id": user_id}).await;
+        update_many_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"group_id": group_id},
+            doc! {"group_name": group_name},
+        )
+        .await
     }
 }
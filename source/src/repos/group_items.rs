@@ -0,0 +1,158 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use shaku::{Component, Interface};
+use slog::Logger;
+
+use proc_macro::HasLogger;
+
+use crate::db::DBIf;
+use crate::logger::AppLoggerIf;
+use crate::repos::db::{
+    delete_many_by, delete_one_by_id, find_first_by, find_many_by, find_one_by, insert_one_into,
+    update_one_by_id,
+};
+use crate::repos::Id;
+
+pub const COLLECTION: &str = "group_items";
+
+#[derive(Serialize)]
+pub struct InsertGroupItem {
+    pub user_id: Id,
+    pub group_id: Id,
+    pub stack_id: Id,
+    pub order_key: String,
+}
+
+/// Stack item filed into a group
+#[derive(Debug, Deserialize)]
+pub struct GroupItem {
+    #[serde(rename = "_id")]
+    pub id: Id,
+    pub user_id: Id,
+    pub group_id: Id,
+    pub stack_id: Id,
+    /// see `services::ordering::key_between`
+    pub order_key: String,
+}
+
+#[async_trait]
+pub trait GroupItemsRepoIf: Interface {
+    async fn insert(&self, item: &InsertGroupItem) -> Id;
+    async fn find_by_group_id_and_stack_id(
+        &self,
+        group_id: &Id,
+        stack_id: &Id,
+    ) -> Option<GroupItem>;
+    async fn find_by_stacks_ids(&self, stacks_ids: &Vec<Id>) -> Vec<GroupItem>;
+    /// returns group items sorted by order
+    async fn get_by_group_id(&self, group_id: &Id) -> Vec<GroupItem>;
+    /// Первый элемент группы с ключом больше `order_key`, или первый элемент если `None`
+    async fn find_first_after(&self, group_id: &Id, order_key: Option<&str>) -> Option<GroupItem>;
+    async fn set_order_key(&self, id: &Id, order_key: &str) -> bool;
+    async fn delete(&self, id: &Id) -> bool;
+    async fn delete_by_group_id(&self, group_id: &Id) -> bool;
+    async fn delete_by_stack_id(&self, stack_id: &Id) -> bool;
+}
+
+#[shaku(interface = GroupItemsRepoIf)]
+#[derive(Component, HasLogger)]
+pub struct GroupItemsRepo {
+    #[shaku(inject)]
+    db: Arc<dyn DBIf>,
+
+    #[logger]
+    #[shaku(inject)]
+    app_logger: Arc<dyn AppLoggerIf>,
+}
+
+#[async_trait]
+impl GroupItemsRepoIf for GroupItemsRepo {
+    async fn insert(&self, item: &InsertGroupItem) -> Id {
+        insert_one_into(&self.db.get(), COLLECTION, item, self.logger()).await
+    }
+
+    async fn find_by_group_id_and_stack_id(
+        &self,
+        group_id: &Id,
+        stack_id: &Id,
+    ) -> Option<GroupItem> {
+        find_one_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"group_id": group_id.oid(), "stack_id": stack_id.oid()},
+            self.logger(),
+        )
+        .await
+    }
+
+    async fn find_by_stacks_ids(&self, stacks_ids: &Vec<Id>) -> Vec<GroupItem> {
+        let stacks_ids: Vec<ObjectId> = stacks_ids.iter().map(|id| id.oid()).collect();
+
+        find_many_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"stack_id": {"$in": stacks_ids}},
+            self.logger(),
+        )
+        .await
+    }
+
+    async fn get_by_group_id(&self, group_id: &Id) -> Vec<GroupItem> {
+        let mut items: Vec<GroupItem> = find_many_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"group_id": group_id.oid()},
+            self.logger(),
+        )
+        .await;
+        items.sort_by(|a, b| a.order_key.cmp(&b.order_key));
+
+        items
+    }
+
+    async fn find_first_after(&self, group_id: &Id, order_key: Option<&str>) -> Option<GroupItem> {
+        find_first_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"group_id": group_id.oid(), "order_key": {"$gt": order_key.unwrap_or("")}},
+            doc! {"order_key": 1},
+            self.logger(),
+        )
+        .await
+    }
+
+    async fn set_order_key(&self, id: &Id, order_key: &str) -> bool {
+        update_one_by_id(
+            &self.db.get(),
+            COLLECTION,
+            id,
+            doc! {"order_key": order_key},
+        )
+        .await
+    }
+
+    async fn delete(&self, id: &Id) -> bool {
+        delete_one_by_id(&self.db.get(), COLLECTION, id).await
+    }
+
+    async fn delete_by_group_id(&self, group_id: &Id) -> bool {
+        delete_many_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"group_id": group_id.oid()},
+        )
+        .await
+    }
+
+    async fn delete_by_stack_id(&self, stack_id: &Id) -> bool {
+        delete_many_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"stack_id": stack_id.oid()},
+        )
+        .await
+    }
+}
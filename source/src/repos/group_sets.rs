@@ -10,7 +10,10 @@ use proc_macro::HasLogger;
 
 use crate::db::DBIf;
 use crate::logger::AppLoggerIf;
-use crate::repos::db::{delete_many_by, find_many_by, find_one_by, insert_many_into, paged_find_many_by, PaginationOptions, find_one_by_id};
+use crate::repos::db::{
+    count_by, delete_many_by, find_first_by, find_many_by, find_one_by, find_one_by_id,
+    insert_one_into, paged_find_many_by, update_many_by, update_one_by_id, PaginationOptions,
+};
 use crate::repos::Id;
 
 pub const COLLECTION: &str = "group_sets";
@@ -21,7 +24,7 @@ pub struct InsertGroupSetItem {
     pub group_id: Id,
     pub group_name: String,
     pub set_name: String,
-    pub order: i32,
+    pub order_key: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -32,7 +35,8 @@ pub struct GroupSetItem {
     pub group_id: Id,
     pub group_name: String,
     pub set_name: String,
-    pub order: i32,
+    /// see `services::ordering::key_between`
+    pub order_key: String,
 }
 
 #[async_trait]
@@ -41,7 +45,7 @@ pub trait GroupSetsRepoIf: Interface {
 
     async fn find_by_group_id(&self, group_id: &Id) -> Option<GroupSetItem>;
 
-    async fn insert(&self, items: Vec<&InsertGroupSetItem>);
+    async fn insert(&self, item: &InsertGroupSetItem) -> Id;
 
     async fn find_by_user_id_set_name_and_group_name(
         &self,
@@ -50,8 +54,31 @@ pub trait GroupSetsRepoIf: Interface {
         group_name: &str,
     ) -> Option<GroupSetItem>;
 
+    async fn find_by_set_name_and_group_id(
+        &self,
+        user_id: &Id,
+        set_name: &str,
+        group_id: &Id,
+    ) -> Option<GroupSetItem>;
+
+    /// Первая группа сета с ключом больше `order_key`, или первая группа сета если `None`
+    async fn find_first_after(
+        &self,
+        user_id: &Id,
+        set_name: &str,
+        order_key: Option<&str>,
+    ) -> Option<GroupSetItem>;
+
+    async fn count_before(&self, user_id: &Id, set_name: &str, order_key: &str) -> i64;
+
+    async fn set_order_key(&self, id: &Id, order_key: &str) -> bool;
+
+    async fn move_to_set(&self, id: &Id, set_name: &str, order_key: &str) -> bool;
+
     async fn get_by_user_id_and_set_name(&self, user_id: &Id, set_name: &str) -> Vec<GroupSetItem>;
 
+    async fn count_by_user_id_and_set_name(&self, user_id: &Id, set_name: &str) -> i64;
+
     async fn get_paged_by_user_id_and_set_name(
         &self,
         user_id: &Id,
@@ -60,7 +87,15 @@ pub trait GroupSetsRepoIf: Interface {
         limit: i32,
     ) -> Vec<GroupSetItem>;
 
-    async fn remove_by_set_name_and_user_id(&self, set_name: &str, user_id: &Id);
+    async fn remove_by_group_id(&self, group_id: &Id) -> bool;
+
+    /// Обновляет копию имени группы, `group_name` хранится в каждом элементе сета
+    async fn rename_group(&self, group_id: &Id, group_name: &str) -> bool;
+
+    /// Переименовывает сет во всех его элементах, `set_name` хранится в каждом
+    async fn rename_set(&self, user_id: &Id, set_name: &str, new_name: &str) -> bool;
+
+    async fn remove_by_user_id_and_set_name(&self, user_id: &Id, set_name: &str) -> bool;
 }
 
 #[shaku(interface = GroupSetsRepoIf)]
@@ -86,8 +121,8 @@ impl GroupSetsRepoIf for GroupSetsRepo {
         find_one_by(&self.db.get(), COLLECTION, doc! {"group_id": group_id}, self.logger()).await
     }
 
-    async fn insert(&self, items: Vec<&InsertGroupSetItem>) {
-        insert_many_into(&self.db.get(), COLLECTION, items, self.logger()).await;
+    async fn insert(&self, item: &InsertGroupSetItem) -> Id {
+        insert_one_into(&self.db.get(), COLLECTION, item, self.logger()).await
     }
 
     async fn find_by_user_id_set_name_and_group_name(
@@ -111,15 +146,102 @@ impl GroupSetsRepoIf for GroupSetsRepo {
         .await
     }
 
+    async fn find_by_set_name_and_group_id(
+        &self,
+        user_id: &Id,
+        set_name: &str,
+        group_id: &Id,
+    ) -> Option<GroupSetItem> {
+        let user_id: ObjectId = user_id.clone().into();
+        let group_id: ObjectId = group_id.clone().into();
+
+        find_one_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"user_id": user_id, "set_name": set_name, "group_id": group_id},
+            self.logger(),
+        )
+        .await
+    }
+
+    async fn find_first_after(
+        &self,
+        user_id: &Id,
+        set_name: &str,
+        order_key: Option<&str>,
+    ) -> Option<GroupSetItem> {
+        let user_id: ObjectId = user_id.clone().into();
+
+        find_first_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {
+                "user_id": user_id,
+                "set_name": set_name,
+                "order_key": {"$gt": order_key.unwrap_or("")},
+            },
+            doc! {"order_key": 1},
+            self.logger(),
+        )
+        .await
+    }
+
+    async fn count_before(&self, user_id: &Id, set_name: &str, order_key: &str) -> i64 {
+        let user_id: ObjectId = user_id.clone().into();
+
+        count_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"user_id": user_id, "set_name": set_name, "order_key": {"$lt": order_key}},
+            self.logger(),
+        )
+        .await
+    }
+
+    async fn set_order_key(&self, id: &Id, order_key: &str) -> bool {
+        update_one_by_id(
+            &self.db.get(),
+            COLLECTION,
+            id,
+            doc! {"order_key": order_key},
+        )
+        .await
+    }
+
+    async fn move_to_set(&self, id: &Id, set_name: &str, order_key: &str) -> bool {
+        update_one_by_id(
+            &self.db.get(),
+            COLLECTION,
+            id,
+            doc! {"set_name": set_name, "order_key": order_key},
+        )
+        .await
+    }
+
     async fn get_by_user_id_and_set_name(&self, user_id: &Id, set_name: &str) -> Vec<GroupSetItem> {
         let user_id: ObjectId = user_id.clone().into();
 
-        find_many_by(
+        let mut items: Vec<GroupSetItem> = find_many_by(
             &self.db.get(),
             COLLECTION,
             doc! {"set_name": set_name, "user_id": user_id},
             self.logger(),
         )
+        .await;
+        items.sort_by(|a, b| a.order_key.cmp(&b.order_key));
+
+        items
+    }
+
+    async fn count_by_user_id_and_set_name(&self, user_id: &Id, set_name: &str) -> i64 {
+        let user_id: ObjectId = user_id.clone().into();
+
+        count_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"user_id": user_id, "set_name": set_name},
+            self.logger(),
+        )
         .await
     }
 
@@ -140,19 +262,50 @@ impl GroupSetsRepoIf for GroupSetsRepo {
             PaginationOptions {
                 offset: offset as i64,
                 limit: limit as i64,
+                sort: Some(doc! {"order_key": 1}),
             },
         )
         .await
     }
 
-    async fn remove_by_set_name_and_user_id(&self, set_name: &str, user_id: &Id) {
+    async fn remove_by_group_id(&self, group_id: &Id) -> bool {
+        let group_id: ObjectId = group_id.clone().into();
+
+        delete_many_by(&self.db.get(), COLLECTION, doc! {"group_id": group_id}).await
+    }
+
+    async fn rename_set(&self, user_id: &Id, set_name: &str, new_name: &str) -> bool {
+        let user_id: ObjectId = user_id.clone().into();
+
+        update_many_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"user_id": user_id, "set_name": set_name},
+            doc! {"set_name": new_name},
+        )
+        .await
+    }
+
+    async fn remove_by_user_id_and_set_name(&self, user_id: &Id, set_name: &str) -> bool {
         let user_id: ObjectId = user_id.clone().into();
 
         delete_many_by(
             &self.db.get(),
             COLLECTION,
-            doc! {"set_name": set_name, "user_id": user_id},
+            doc! {"user_id": user_id, "set_name": set_name},
         )
-        .await;
+        .await
+    }
+
+    async fn rename_group(&self, group_id: &Id, group_name: &str) -> bool {
+        let group_id: ObjectId = group_id.clone().into();
+
+        update_many_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"group_id": group_id},
+            doc! {"group_name": group_name},
+        )
+        .await
     }
 }
@@ -39,6 +39,7 @@ pub trait GroupsRepoIf: Interface {
     async fn get_by_creator_id_and_name(&self, creator_id: &Id, name: &str) -> Vec<Group>;
     async fn insert(&self, group: InsertGroup) -> Group;
     async fn mark_removed(&self, group_id: &Id) -> bool;
+    async fn rename(&self, group_id: &Id, name: &str) -> bool;
 }
 
 #[shaku(interface = GroupsRepoIf)]
@@ -92,4 +93,8 @@ impl GroupsRepoIf for GroupsRepo {
         )
         .await
     }
+
+    async fn rename(&self, group_id: &Id, name: &str) -> bool {
+        update_one_by_id(&self.db.get(), COLLECTION, group_id, doc! {"name": name}).await
+    }
 }
@@ -1,9 +1,10 @@
 use crate::db::DBIf;
 use crate::logger::AppLoggerIf;
-use crate::repos::db::insert_many_into;
+use crate::repos::db::{delete_many_by, insert_many_into, update_one_by_id};
 use crate::repos::db::{find_many_by, find_many_by_ids};
 use crate::repos::Id;
 use crate::utils::Refs;
+use async_graphql::Enum;
 use async_trait::async_trait;
 use bson::oid::ObjectId;
 use proc_macro::HasLogger;
@@ -14,6 +15,26 @@ use std::sync::Arc;
 
 pub const COLLECTION: &str = "marks";
 
+/// What the marked range means.
+/// Marks stored before kinds appeared are read as highlights
+#[derive(Enum, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkKind {
+    Bold,
+    Italic,
+    Code,
+    /// link target is kept in `href`
+    Link,
+    /// colour is kept in `color`, client default is used if not set
+    Highlight,
+}
+
+impl Default for MarkKind {
+    fn default() -> Self {
+        MarkKind::Highlight
+    }
+}
+
 #[derive(Clone, Deserialize, Debug)]
 pub struct Mark {
     #[serde(rename = "_id")]
@@ -21,6 +42,12 @@ pub struct Mark {
     pub block_id: Id,
     pub from: i32,
     pub to: i32,
+    #[serde(default)]
+    pub kind: MarkKind,
+    #[serde(default)]
+    pub href: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -28,6 +55,9 @@ pub struct InsertMark {
     pub block_id: Id,
     pub from: i32,
     pub to: i32,
+    pub kind: MarkKind,
+    pub href: Option<String>,
+    pub color: Option<String>,
 }
 
 #[async_trait]
@@ -35,6 +65,8 @@ pub trait MarksRepoIf: Interface {
     async fn insert_many(&self, new_marks: Vec<&InsertMark>) -> Vec<Mark>;
     async fn find_by_ids(&self, ids: Vec<&Id>) -> Vec<Mark>;
     async fn find_by_block_id(&self, block_id: &Id) -> Vec<Mark>;
+    async fn update(&self, mark: &Mark) -> bool;
+    async fn delete_many(&self, ids: Vec<&Id>);
 }
 
 #[shaku(interface = MarksRepoIf)]
@@ -86,6 +118,9 @@ impl MarksRepoIf for MarksRepo {
                 block_id: mark.block_id.clone(),
                 from: mark.from,
                 to: mark.to,
+                kind: mark.kind,
+                href: mark.href.clone(),
+                color: mark.color.clone(),
             })
         }
 
@@ -106,4 +141,25 @@ impl MarksRepoIf for MarksRepo {
         )
         .await
     }
+
+    async fn update(&self, mark: &Mark) -> bool {
+        update_one_by_id(
+            &self.db.get(),
+            COLLECTION,
+            &mark.id,
+            doc! {
+                "from": mark.from,
+                "to": mark.to,
+                "kind": bson::to_bson(&mark.kind).unwrap(),
+                "href": bson::to_bson(&mark.href).unwrap(),
+                "color": bson::to_bson(&mark.color).unwrap(),
+            },
+        )
+        .await
+    }
+
+    async fn delete_many(&self, ids: Vec<&Id>) {
+        let ids: Vec<ObjectId> = ids.iter().map(|x| x.oid()).collect();
+        delete_many_by(&self.db.get(), COLLECTION, doc! {"_id": {"$in": ids}}).await;
+    }
 }
@@ -9,9 +9,9 @@ use std::fmt::Display;
 pub mod blocks;
 pub mod db;
 pub mod default_group_sets;
+pub mod group_items;
 pub mod group_sets;
 pub mod groups;
-pub mod groups_ordering;
 pub mod marks;
 pub mod recent_sets;
 pub mod sets;
@@ -1,12 +1,16 @@
 use crate::db::DBIf;
 use crate::logger::AppLoggerIf;
 use crate::repos::db::{
-    delete_many_by, find_many_by, find_one_by, find_one_by_id, insert_many_into,
-    paged_find_many_by, PaginationOptions,
+    delete_many_by, find_one_by, find_one_by_id, insert_many_into, paged_find_many_by,
+    PaginationOptions,
 };
 use crate::repos::{Id, Repo};
+use crate::utils::{deserialize_bson, IntoAppErr, LogErrWith};
 use async_trait::async_trait;
 use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use mongodb::options::{FindOptions, UpdateOptions};
 use proc_macro::HasLogger;
 use serde::{Deserialize, Serialize};
 use shaku::{Component, Interface};
@@ -19,6 +23,7 @@ pub const COLLECTION: &str = "recent_sets";
 pub struct InsertRecentSet {
     pub user_id: Id,
     pub set_id: Id,
+    pub last_used_at: DateTime<Utc>,
 }
 
 #[derive(Deserialize)]
@@ -27,11 +32,20 @@ pub struct RecentSet {
     pub id: Id,
     pub user_id: Id,
     pub set_id: Id,
+    /// when set was opened or modified last time, not set for recents stored before it was tracked
+    #[serde(default)]
+    pub last_used_at: Option<bson::DateTime>,
 }
 
 #[async_trait]
 pub trait RecentSetsRepoIf: Interface + Repo<RecentSet, InsertRecentSet> {
+    /// returns user recents, recently used first.
+    /// Sets used at the same moment go in reverse order they got into recents
     async fn find_by_user_id(&self, id: &Id) -> Vec<RecentSet>;
+    /// Отмечает сет использованным в `used_at`, добавляет его в недавние если его там нет
+    async fn touch(&self, user_id: &Id, set_id: &Id, used_at: DateTime<Utc>);
+    /// Убирает сет из недавних у всех пользователей
+    async fn delete_by_set_id(&self, set_id: &Id) -> bool;
 }
 
 #[shaku(interface = RecentSetsRepoIf)]
@@ -48,15 +62,43 @@ pub struct RecentSetsRepo {
 #[async_trait]
 impl RecentSetsRepoIf for RecentSetsRepo {
     async fn find_by_user_id(&self, id: &Id) -> Vec<RecentSet> {
-        println!("{:#?}", doc! {"user_id": id.oid()});
+        self.db
+            .get()
+            .collection(COLLECTION)
+            .find(
+                Some(doc! {"user_id": id.oid()}),
+                Some(
+                    FindOptions::builder()
+                        .sort(doc! {"last_used_at": -1, "_id": -1})
+                        .build(),
+                ),
+            )
+            .await
+            .log_err_with(self.logger())
+            .into_app_err()
+            .unwrap()
+            .map(|x| deserialize_bson(&x.unwrap()))
+            .collect()
+            .await
+    }
+
+    async fn touch(&self, user_id: &Id, set_id: &Id, used_at: DateTime<Utc>) {
+        self.db
+            .get()
+            .collection(COLLECTION)
+            .update_one(
+                doc! {"user_id": user_id.oid(), "set_id": set_id.oid()},
+                doc! {"$set": {"last_used_at": used_at}},
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await
+            .log_err_with(self.logger())
+            .into_app_err()
+            .unwrap();
+    }
 
-        find_many_by(
-            &self.db.get(),
-            COLLECTION,
-            doc! {"user_id": id.oid()},
-            &self.logger(),
-        )
-        .await
+    async fn delete_by_set_id(&self, set_id: &Id) -> bool {
+        delete_many_by(&self.db.get(), COLLECTION, doc! {"set_id": set_id.oid()}).await
     }
 }
 
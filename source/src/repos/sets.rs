@@ -1,10 +1,13 @@
 use crate::db::DBIf;
 use crate::logger::AppLoggerIf;
-use crate::repos::db::{find_many_by, find_many_by_ids, find_one_by};
-use crate::repos::db::{find_one_by_id, insert_one_into, update_one_by_id};
+use crate::repos::db::{count_by, delete_many_by, delete_one_by_id, find_many_by};
+use crate::repos::db::{find_many_by_ids, find_one_by, find_one_by_id, insert_one_into};
+use crate::repos::db::{paged_find_many_by, update_one_by_id, PaginationOptions};
 use crate::repos::{Id, Repo};
+use async_graphql::Enum;
 use async_trait::async_trait;
 use bson::oid::ObjectId;
+use bson::Document;
 use proc_macro::HasLogger;
 use serde::{Deserialize, Serialize};
 use shaku::{Component, Interface};
@@ -27,9 +30,62 @@ pub struct Set {
     pub creator_id: Id,
 }
 
+/// Order of user sets list, sets are created in `_id` order
+#[derive(Enum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SetsSortBy {
+    NameAsc,
+    NameDesc,
+    CreatedAsc,
+    CreatedDesc,
+}
+
+impl SetsSortBy {
+    fn sort(self) -> Document {
+        match self {
+            SetsSortBy::NameAsc => doc! {"name": 1, "_id": 1},
+            SetsSortBy::NameDesc => doc! {"name": -1, "_id": -1},
+            SetsSortBy::CreatedAsc => doc! {"_id": 1},
+            SetsSortBy::CreatedDesc => doc! {"_id": -1},
+        }
+    }
+}
+
+/// Sets of the creator, which name contains `name_contains` case insensitively if passed
+fn creator_sets(creator_id: &Id, name_contains: Option<&str>) -> Document {
+    let mut criteria = doc! {"creator_id": creator_id.oid()};
+    if let Some(part) = name_contains {
+        criteria.insert("name", doc! {"$regex": escape_regex(part), "$options": "i"});
+    }
+
+    criteria
+}
+
+/// so user input is matched literally
+fn escape_regex(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
 #[async_trait]
 pub trait SetsRepoIf: Interface + Repo<Set, InsertSet> {
     async fn find_one_by_creator_id_and_name(&self, user_id: &Id, name: &str) -> Option<Set>;
+    async fn rename(&self, id: &Id, name: &str) -> bool;
+    async fn get_paged_by_creator_id(
+        &self,
+        creator_id: &Id,
+        name_contains: Option<&str>,
+        sort_by: SetsSortBy,
+        offset: i32,
+        limit: i32,
+    ) -> Vec<Set>;
+    async fn count_by_creator_id(&self, creator_id: &Id, name_contains: Option<&str>) -> i64;
 }
 
 #[shaku(interface = SetsRepoIf)]
@@ -67,11 +123,12 @@ impl Repo<Set, InsertSet> for SetsRepo {
     }
 
     async fn delete(&self, id: &Id) {
-        unimplemented!()
+        delete_one_by_id(&self.db.get(), COLLECTION, id).await;
     }
 
     async fn delete_many(&self, ids: Vec<&Id>) {
-        unimplemented!()
+        let ids: Vec<ObjectId> = ids.iter().map(|x| x.oid()).collect();
+        delete_many_by(&self.db.get(), COLLECTION, doc! {"_id": {"$in": ids}}).await;
     }
 }
 
@@ -86,4 +143,40 @@ impl SetsRepoIf for SetsRepo {
         )
         .await
     }
+
+    async fn rename(&self, id: &Id, name: &str) -> bool {
+        update_one_by_id(&self.db.get(), COLLECTION, id, doc! {"name": name}).await
+    }
+
+    async fn get_paged_by_creator_id(
+        &self,
+        creator_id: &Id,
+        name_contains: Option<&str>,
+        sort_by: SetsSortBy,
+        offset: i32,
+        limit: i32,
+    ) -> Vec<Set> {
+        paged_find_many_by(
+            &self.db.get(),
+            COLLECTION,
+            creator_sets(creator_id, name_contains),
+            self.logger(),
+            PaginationOptions {
+                offset: offset as i64,
+                limit: limit as i64,
+                sort: Some(sort_by.sort()),
+            },
+        )
+        .await
+    }
+
+    async fn count_by_creator_id(&self, creator_id: &Id, name_contains: Option<&str>) -> i64 {
+        count_by(
+            &self.db.get(),
+            COLLECTION,
+            creator_sets(creator_id, name_contains),
+            self.logger(),
+        )
+        .await
+    }
 }
@@ -1,9 +1,11 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use bson::Document;
+use bson::{Bson, Document};
 use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
 use futures::StreamExt;
+use mongodb::options::{FindOneOptions, FindOptions};
 use serde::{Deserialize, Serialize};
 use shaku::{Component, Interface};
 use slog::Logger;
@@ -12,7 +14,10 @@ use proc_macro::HasLogger;
 
 use crate::db::DBIf;
 use crate::logger::AppLoggerIf;
-use crate::repos::db::{find_one_by_id, insert_one_into, link_external_ids};
+use crate::repos::db::{
+    delete_one_by_id, find_many_by, find_many_by_ids, find_one_by_id, insert_one_into,
+    link_external_ids, update_many_by, update_one_by_id,
+};
 use crate::repos::Id;
 use crate::utils::{deserialize_bson, IntoAppErr, LogErrWith};
 
@@ -22,14 +27,41 @@ pub const COLLECTION: &str = "stack";
 pub trait StackRepoIf: Interface {
     async fn insert(&self, stack_item: &NewStackItem) -> StackItem;
     async fn update(&self, stack_item: &StackItem) -> StackItem;
+    /// Moves item to the next version if it is still at `version`.
+    /// `false` means item was changed by someone else
+    async fn increment_version(&self, id: &Id, version: i32) -> bool;
     async fn link_blocks(&self, stack_item: &StackItem, blocks_ids: &Vec<Id>) -> StackItem;
+    async fn find_by_ids(&self, ids: Vec<&Id>) -> Vec<StackItem>;
     async fn link_marks(&self, stack_item: &StackItem, marks_ids: &Vec<Id>) -> StackItem;
+    /// items moved to trash are not returned, last pushed items go first
     async fn find_by_user_id(&self, user_id: Id) -> Vec<StackItem>;
+    /// user items among `ids`, items moved to trash are not returned. Order is arbitrary
+    async fn find_by_user_id_and_ids(&self, user_id: &Id, ids: Vec<&Id>) -> Vec<StackItem>;
+    /// last pushed item which was not popped yet
+    async fn find_top_by_user_id(&self, user_id: Id) -> Option<StackItem>;
+    /// marks the item popped, the item is popped only once even if several clients
+    /// pop simultaneously. `false` means it was already popped or moved to trash
+    async fn pop(&self, id: &Id) -> bool;
+    /// puts item back on top of the stack, `false` means item is in trash
+    async fn push(&self, id: &Id, pushed_at: DateTime<Utc>) -> bool;
+    /// items moved to trash are not returned
     async fn find_by_user_id_and_stack_item_id(
         &self,
         user_id: Id,
         stack_item_id: Id,
     ) -> Option<StackItem>;
+    /// returns items from trash, recently removed first
+    async fn find_removed_by_user_id(&self, user_id: Id) -> Vec<StackItem>;
+    async fn find_removed_by_user_id_and_stack_item_id(
+        &self,
+        user_id: Id,
+        stack_item_id: Id,
+    ) -> Option<StackItem>;
+    /// moves item to trash
+    async fn mark_removed(&self, id: &Id, removed_at: DateTime<Utc>) -> bool;
+    /// moves item back from trash
+    async fn unmark_removed(&self, id: &Id) -> bool;
+    async fn delete(&self, id: &Id) -> bool;
 }
 
 #[shaku(interface = StackRepoIf)]
@@ -49,6 +81,9 @@ pub struct NewStackItem {
     pub blocks_ids: Vec<Id>,
     pub marks_ids: Vec<Id>,
     pub version: i32,
+    pub created_at: bson::DateTime,
+    pub pushed_at: bson::DateTime,
+    pub popped: bool,
 }
 
 #[derive(Serialize, Debug)]
@@ -69,6 +104,26 @@ pub struct StackItem {
     pub id: Id,
     pub blocks_ids: Vec<Id>,
     pub marks_ids: Vec<Id>,
+    #[serde(default)]
+    pub version: i32,
+    /// items added before timestamps were stored have none.
+    /// Stack position fields are changed only by `push` and `pop`,
+    /// so they are not written by `update`
+    #[serde(default, skip_serializing)]
+    pub created_at: Option<bson::DateTime>,
+    /// when item was added or pushed back on top of the stack
+    #[serde(default, skip_serializing)]
+    pub pushed_at: Option<bson::DateTime>,
+    /// popped items are processed and are not in the stack anymore
+    /// until pushed back, though they are still listed in user stack
+    #[serde(default, skip_serializing)]
+    pub popped: bool,
+}
+
+/// Last pushed items go first, items stored before `pushed_at` was introduced
+/// have none and go last in the order they were added
+fn lifo_sort() -> Document {
+    doc! {"pushed_at": -1, "_id": -1}
 }
 
 #[async_trait]
@@ -81,20 +136,42 @@ impl StackRepoIf for StackRepo {
     }
 
     async fn update(&self, stack_item: &StackItem) -> StackItem {
-        let _id: ObjectId = stack_item.id.clone().into();
-
-        let _doc: Document = bson::to_bson(&stack_item)
+        let mut doc: Document = bson::to_bson(&stack_item)
             .unwrap()
             .as_document()
             .unwrap()
             .clone();
+        doc.remove("_id");
+
+        update_one_by_id(&self.db.get(), COLLECTION, &stack_item.id, doc).await;
+
+        find_one_by_id(&self.db.get(), COLLECTION, &stack_item.id, self.logger())
+            .await
+            .unwrap()
+    }
 
-        // self.db
-        //     .get()
-        //     .collection("stack")
-        //     .update_one(doc! {"_id": id}, doc, None);
+    async fn increment_version(&self, id: &Id, version: i32) -> bool {
+        let id: ObjectId = id.clone().into();
+        // items stored before versioning have no version at all
+        let version_criteria = match version {
+            0 => bson!({"$in": [0, Bson::Null]}),
+            v => bson!(v),
+        };
 
-        unimplemented!()
+        self.db
+            .get()
+            .collection(COLLECTION)
+            .update_one(
+                doc! {"_id": id, "version": version_criteria},
+                doc! {"$set": {"version": version + 1}},
+                None,
+            )
+            .await
+            .log_err_with(self.logger())
+            .into_app_err()
+            .unwrap()
+            .modified_count
+            > 0
     }
 
     async fn link_blocks(&self, stack_item: &StackItem, blocks_ids: &Vec<Id>) -> StackItem {
@@ -127,13 +204,20 @@ impl StackRepoIf for StackRepo {
             .unwrap()
     }
 
+    async fn find_by_ids(&self, ids: Vec<&Id>) -> Vec<StackItem> {
+        find_many_by_ids(&self.db.get(), COLLECTION, ids, self.logger()).await
+    }
+
     async fn find_by_user_id(&self, user_id: Id) -> Vec<StackItem> {
         let user_id: ObjectId = user_id.into();
 
         self.db
             .get()
             .collection(COLLECTION)
-            .find(Some(doc! {"user_id": user_id}), None)
+            .find(
+                Some(doc! {"user_id": user_id, "removed": {"$ne": true}}),
+                Some(FindOptions::builder().sort(lifo_sort()).build()),
+            )
             .await
             .log_err_with(self.logger())
             .into_app_err()
@@ -143,6 +227,59 @@ impl StackRepoIf for StackRepo {
             .await
     }
 
+    async fn find_by_user_id_and_ids(&self, user_id: &Id, ids: Vec<&Id>) -> Vec<StackItem> {
+        let ids: Vec<ObjectId> = ids.into_iter().map(|id| id.oid()).collect();
+
+        find_many_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"_id": {"$in": ids}, "user_id": user_id.oid(), "removed": {"$ne": true}},
+            self.logger(),
+        )
+        .await
+    }
+
+    async fn find_top_by_user_id(&self, user_id: Id) -> Option<StackItem> {
+        let user_id: ObjectId = user_id.into();
+
+        self.db
+            .get()
+            .collection(COLLECTION)
+            .find_one(
+                Some(doc! {"user_id": user_id, "removed": {"$ne": true}, "popped": {"$ne": true}}),
+                Some(FindOneOptions::builder().sort(lifo_sort()).build()),
+            )
+            .await
+            .log_err_with(self.logger())
+            .into_app_err()
+            .unwrap()
+            .map(|u| deserialize_bson(&u))
+    }
+
+    async fn pop(&self, id: &Id) -> bool {
+        let id: ObjectId = id.clone().into();
+
+        update_many_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"_id": id, "removed": {"$ne": true}, "popped": {"$ne": true}},
+            doc! {"popped": true},
+        )
+        .await
+    }
+
+    async fn push(&self, id: &Id, pushed_at: DateTime<Utc>) -> bool {
+        let id: ObjectId = id.clone().into();
+
+        update_many_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"_id": id, "removed": {"$ne": true}},
+            doc! {"popped": false, "pushed_at": pushed_at},
+        )
+        .await
+    }
+
     async fn find_by_user_id_and_stack_item_id(
         &self,
         user_id: Id,
@@ -154,11 +291,79 @@ impl StackRepoIf for StackRepo {
         self.db
             .get()
             .collection(COLLECTION)
-            .find_one(Some(doc! {"user_id": user_id, "_id": stack_item_id}), None)
+            .find_one(
+                Some(doc! {"user_id": user_id, "_id": stack_item_id, "removed": {"$ne": true}}),
+                None,
+            )
+            .await
+            .log_err_with(self.logger())
+            .into_app_err()
+            .unwrap()
+            .map(|u| deserialize_bson(&u))
+    }
+
+    async fn find_removed_by_user_id(&self, user_id: Id) -> Vec<StackItem> {
+        let user_id: ObjectId = user_id.into();
+
+        self.db
+            .get()
+            .collection(COLLECTION)
+            .find(
+                Some(doc! {"user_id": user_id, "removed": true}),
+                Some(FindOptions::builder().sort(doc! {"removed_at": -1}).build()),
+            )
+            .await
+            .log_err_with(self.logger())
+            .into_app_err()
+            .unwrap()
+            .map(|x| deserialize_bson(&x.unwrap()))
+            .collect()
+            .await
+    }
+
+    async fn find_removed_by_user_id_and_stack_item_id(
+        &self,
+        user_id: Id,
+        stack_item_id: Id,
+    ) -> Option<StackItem> {
+        let user_id: ObjectId = user_id.into();
+        let stack_item_id: ObjectId = stack_item_id.into();
+
+        self.db
+            .get()
+            .collection(COLLECTION)
+            .find_one(
+                Some(doc! {"user_id": user_id, "_id": stack_item_id, "removed": true}),
+                None,
+            )
             .await
             .log_err_with(self.logger())
             .into_app_err()
             .unwrap()
             .map(|u| deserialize_bson(&u))
     }
+
+    async fn mark_removed(&self, id: &Id, removed_at: DateTime<Utc>) -> bool {
+        update_one_by_id(
+            &self.db.get(),
+            COLLECTION,
+            id,
+            doc! {"removed": true, "removed_at": removed_at},
+        )
+        .await
+    }
+
+    async fn unmark_removed(&self, id: &Id) -> bool {
+        update_one_by_id(
+            &self.db.get(),
+            COLLECTION,
+            id,
+            doc! {"removed": false, "removed_at": bson::Bson::Null},
+        )
+        .await
+    }
+
+    async fn delete(&self, id: &Id) -> bool {
+        delete_one_by_id(&self.db.get(), COLLECTION, id).await
+    }
 }
@@ -1,7 +1,8 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use serde::Serialize;
+use bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
 use shaku::{Component, Interface};
 use slog::Logger;
 
@@ -9,7 +10,8 @@ use proc_macro::HasLogger;
 
 use crate::db::DBIf;
 use crate::logger::AppLoggerIf;
-use crate::repos::db::insert_many_into;
+use crate::repos::db::{delete_many_by, find_many_by, find_one_by, insert_many_into};
+use crate::repos::marks::MarkKind;
 use crate::repos::Id;
 use crate::services::stack::{Block, Mark};
 
@@ -41,6 +43,9 @@ pub struct InsertHistoryMark {
     pub mark_id: Id,
     pub from: i32,
     pub to: i32,
+    pub kind: MarkKind,
+    pub href: Option<String>,
+    pub color: Option<String>,
 }
 
 impl From<Mark> for InsertHistoryMark {
@@ -49,13 +54,51 @@ impl From<Mark> for InsertHistoryMark {
             mark_id: mark.id,
             from: mark.from,
             to: mark.to,
+            kind: mark.kind,
+            href: mark.href,
+            color: mark.color,
         }
     }
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct HistoryBlock {
+    #[serde(rename = "_id")]
+    pub id: Id,
+    pub stack_id: Id,
+    pub block_id: Id,
+    pub version: i32,
+    pub text: String,
+    pub marks: Vec<HistoryMark>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct HistoryMark {
+    pub mark_id: Id,
+    pub from: i32,
+    pub to: i32,
+    #[serde(default)]
+    pub kind: MarkKind,
+    #[serde(default)]
+    pub href: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
 #[async_trait]
 pub trait StackHistoryRepoIf: Interface {
     async fn insert_many(&self, blocks: Vec<&InsertHistoryBlock>);
+
+    /// returns block versions sorted by version
+    async fn find_by_block_id(&self, block_id: &Id) -> Vec<HistoryBlock>;
+
+    async fn find_by_block_id_and_version(
+        &self,
+        block_id: &Id,
+        version: i32,
+    ) -> Option<HistoryBlock>;
+
+    async fn delete_by_stack_id(&self, stack_id: &Id);
 }
 
 #[shaku(interface = StackHistoryRepoIf)]
@@ -74,4 +117,40 @@ impl StackHistoryRepoIf for StackHistoryRepo {
     async fn insert_many(&self, blocks: Vec<&InsertHistoryBlock>) {
         insert_many_into(&self.db.get(), COLLECTION, blocks, &self.logger()).await;
     }
+
+    async fn find_by_block_id(&self, block_id: &Id) -> Vec<HistoryBlock> {
+        let block_id: ObjectId = block_id.clone().into();
+
+        let mut versions: Vec<HistoryBlock> = find_many_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"block_id": block_id},
+            self.logger(),
+        )
+        .await;
+        versions.sort_by_key(|v| v.version);
+
+        versions
+    }
+
+    async fn find_by_block_id_and_version(
+        &self,
+        block_id: &Id,
+        version: i32,
+    ) -> Option<HistoryBlock> {
+        let block_id: ObjectId = block_id.clone().into();
+
+        find_one_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"block_id": block_id, "version": version},
+            self.logger(),
+        )
+        .await
+    }
+
+    async fn delete_by_stack_id(&self, stack_id: &Id) {
+        let stack_id: ObjectId = stack_id.clone().into();
+        delete_many_by(&self.db.get(), COLLECTION, doc! {"stack_id": stack_id}).await;
+    }
 }
@@ -4,7 +4,9 @@ use async_graphql::SimpleObject;
 use async_trait::async_trait;
 use bson::Document;
 use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac, NewMac};
 use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 use shaku::{Component, Interface};
 use slog::Logger;
 
@@ -12,16 +14,40 @@ use proc_macro::HasLogger;
 
 use crate::db::DBIf;
 use crate::logger::AppLoggerIf;
+use crate::repos::db::{delete_many_by, find_many_by, find_one_by, update_many_by};
 use crate::repos::Id;
 use crate::utils::{deserialize_bson, IntoAppErr, LogErrWith};
 
 pub const COLLECTION: &str = "tokens";
 
+///
+/// Токены храним только HMAC-SHA256 хешами, чтобы прочитав базу нельзя было зайти под юзером.
+/// Ищем пару тоже по хешу
+///
+pub fn hash_token(secret: &str, token: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC accepts any key");
+    mac.update(token.as_bytes());
+
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
 #[async_trait]
 pub trait TokensRepoIf: Interface {
     async fn find_by_access(&self, access: &str) -> Option<TokenPair>;
     async fn find_by_refresh(&self, refresh: &str) -> Option<TokenPair>;
     async fn insert(&self, tokens: &TokenPair);
+    async fn find_by_user_id(&self, user_id: &Id) -> Vec<TokenPair>;
+    async fn find_by_id_and_user_id(&self, id: &Id, user_id: &Id) -> Option<TokenPair>;
+    async fn delete_by_user_id(&self, user_id: &Id) -> bool;
+    /// `false` if pair is already rotated, so only one refresh of the pair succeeds
+    async fn mark_rotated(&self, id: &Id) -> bool;
+    /// deletes every pair of the family, pairs issued before families had none
+    /// and are the family of their own
+    async fn delete_family(&self, family: &Id) -> bool;
 }
 
 #[shaku(interface = TokensRepoIf)]
@@ -33,16 +59,54 @@ pub struct TokensRepo {
     #[logger]
     #[shaku(inject)]
     app_logger: Arc<dyn AppLoggerIf>,
+
+    #[shaku(no_default)]
+    token_secret: String,
+}
+
+/// Client the session was started from, captured by the `graphql` handler
+#[derive(Debug, Clone, Default)]
+pub struct SessionMeta {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 pub struct TokenPair {
+    /// session id, not set until pair is stored
+    #[serde(rename = "_id", default, skip_serializing)]
+    #[graphql(skip)]
+    pub id: Option<Id>,
+    /// raw tokens are known only when pair is issued, stored pair has their hashes
+    #[serde(skip)]
     pub access: String,
+    #[serde(skip)]
     pub refresh: String,
     pub access_lifetime: DateTime<Utc>,
     pub refresh_lifetime: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
     pub user_id: Id,
+    #[serde(default)]
+    #[graphql(skip)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    #[graphql(skip)]
+    pub ip: Option<String>,
+    /// pairs refreshed from the same login, one family is one session
+    #[serde(default)]
+    #[graphql(skip)]
+    pub family: Option<Id>,
+    /// pair was refreshed, its tokens are not valid anymore.
+    /// It is kept to detect the refresh token reuse until the refresh token expires
+    #[serde(default)]
+    #[graphql(skip)]
+    pub rotated: bool,
+}
+
+impl TokenPair {
+    pub fn family_id(&self) -> Option<Id> {
+        self.family.clone().or(self.id.clone())
+    }
 }
 
 #[async_trait]
@@ -51,7 +115,10 @@ impl TokensRepoIf for TokensRepo {
         self.db
             .get()
             .collection(COLLECTION)
-            .find_one(Some(doc! {"access": access}), None)
+            .find_one(
+                Some(doc! {"access_hash": hash_token(&self.token_secret, access)}),
+                None,
+            )
             .await
             .log_err_with(self.logger())
             .into_app_err()
@@ -63,7 +130,10 @@ impl TokensRepoIf for TokensRepo {
         self.db
             .get()
             .collection(COLLECTION)
-            .find_one(Some(doc! {"refresh": refresh}), None)
+            .find_one(
+                Some(doc! {"refresh_hash": hash_token(&self.token_secret, refresh)}),
+                None,
+            )
             .await
             .log_err_with(self.logger())
             .into_app_err()
@@ -72,11 +142,22 @@ impl TokensRepoIf for TokensRepo {
     }
 
     async fn insert(&self, tokens: &TokenPair) {
-        let inserting_doc: Document = bson::to_bson(&tokens)
+        let mut inserting_doc: Document = bson::to_bson(&tokens)
             .unwrap()
             .as_document()
             .unwrap()
             .clone();
+        inserting_doc.insert(
+            "access_hash",
+            hash_token(&self.token_secret, &tokens.access),
+        );
+        inserting_doc.insert(
+            "refresh_hash",
+            hash_token(&self.token_secret, &tokens.refresh),
+        );
+        // `refresh_lifetime` хранится строкой, а TTL индекс удаляет только по дате.
+        // Пара не нужна когда refresh токен протух, даже ротированная
+        inserting_doc.insert("expires_at", tokens.refresh_lifetime);
 
         self.db
             .get()
@@ -86,4 +167,47 @@ impl TokensRepoIf for TokensRepo {
             .log_err_with(self.logger())
             .unwrap();
     }
+
+    async fn find_by_user_id(&self, user_id: &Id) -> Vec<TokenPair> {
+        find_many_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"user_id": user_id.oid()},
+            self.logger(),
+        )
+        .await
+    }
+
+    async fn find_by_id_and_user_id(&self, id: &Id, user_id: &Id) -> Option<TokenPair> {
+        find_one_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"_id": id.oid(), "user_id": user_id.oid()},
+            self.logger(),
+        )
+        .await
+    }
+
+    async fn delete_by_user_id(&self, user_id: &Id) -> bool {
+        delete_many_by(&self.db.get(), COLLECTION, doc! {"user_id": user_id.oid()}).await
+    }
+
+    async fn mark_rotated(&self, id: &Id) -> bool {
+        update_many_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"_id": id.oid(), "rotated": {"$ne": true}},
+            doc! {"rotated": true},
+        )
+        .await
+    }
+
+    async fn delete_family(&self, family: &Id) -> bool {
+        delete_many_by(
+            &self.db.get(),
+            COLLECTION,
+            doc! {"$or": [{"family": family.oid()}, {"_id": family.oid()}]},
+        )
+        .await
+    }
 }
@@ -1,11 +1,14 @@
+use crate::channels::sessions::{SessionEvent, SessionsChannelIf};
 use crate::errors::AppError;
 use crate::logger::AppLoggerIf;
-use crate::repos::tokens::{TokenPair, TokensRepoIf};
+use crate::repos::tokens::{SessionMeta, TokenPair, TokensRepoIf};
 use crate::repos::users::{NewUser, User, UsersRepoIf};
 use crate::repos::Id;
 use crate::utils::{AppResult, IntoAppErr, LogErrWith, OkOrUnauthorized};
+use async_graphql::SimpleObject;
 use async_trait::async_trait;
 use bcrypt::{hash, verify, DEFAULT_COST};
+use bson::oid::ObjectId;
 use chrono::{DateTime, Duration, Utc};
 use proc_macro::HasLogger;
 use shaku::{Component, Interface};
@@ -13,17 +16,48 @@ use slog::Logger;
 use std::sync::Arc;
 use uuid::Uuid;
 
+/// Active token pair of user as it is shown to them, tokens themselves are not exposed
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct Session {
+    pub id: Id,
+    pub created_at: DateTime<Utc>,
+    /// session ends then if tokens are not refreshed
+    pub refresh_lifetime: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    /// session the request is made from
+    pub current: bool,
+}
+
 #[async_trait]
 pub trait AuthServiceIf: Interface {
     async fn login(
         &self,
         username: String,
         password: String,
+        meta: SessionMeta,
         now: DateTime<Utc>,
     ) -> AppResult<TokenPair>;
     async fn register(&self, login: String, password: String) -> AppResult<()>;
+    /// Issues new pair of the same family and invalidates the refreshed one.
+    /// Refresh token presented once again revokes the whole family
     async fn refresh_token(&self, refresh: &str, now: DateTime<Utc>) -> AppResult<TokenPair>;
     async fn validate_access(&self, access: &str, now: DateTime<Utc>) -> AppResult<User>;
+    /// Same as `validate_access` but also returns when the access expires,
+    /// for connections living longer than a single request
+    async fn validate_access_with_expiry(
+        &self,
+        access: &str,
+        now: DateTime<Utc>,
+    ) -> AppResult<(User, DateTime<Utc>)>;
+    /// revokes token pair of the access
+    async fn logout(&self, access: &str) -> AppResult<()>;
+    /// revokes all user token pairs, including the current one
+    async fn logout_everywhere(&self, user: &User) -> AppResult<()>;
+    /// sessions which can be refreshed, recently started first
+    async fn sessions(&self, user: &User, current_access: &str, now: DateTime<Utc>)
+        -> Vec<Session>;
+    async fn revoke_session(&self, user: &User, id: &Id) -> AppResult<()>;
 }
 
 #[derive(Component, HasLogger)]
@@ -35,6 +69,9 @@ pub struct AuthService {
     #[shaku(inject)]
     tokens_repo: Arc<dyn TokensRepoIf>,
 
+    #[shaku(inject)]
+    sessions_channel: Arc<dyn SessionsChannelIf>,
+
     #[logger]
     #[shaku(inject)]
     app_logger: Arc<dyn AppLoggerIf>,
@@ -49,19 +86,13 @@ pub struct AuthService {
     pwd_min_len: u32,
 }
 
-impl AuthService {
-    async fn find_user_by_access(&self, access: &str) -> Option<User> {
-        let token = self.tokens_repo.find_by_access(access).await?;
-        self.users_repo.find(&token.user_id).await
-    }
-}
-
 #[async_trait]
 impl AuthServiceIf for AuthService {
     async fn login(
         &self,
         username: String,
         password: String,
+        meta: SessionMeta,
         now: DateTime<Utc>,
     ) -> AppResult<TokenPair> {
         let user = self
@@ -74,7 +105,8 @@ impl AuthServiceIf for AuthService {
             return Err(AppError::login_failed());
         }
 
-        let token = self.construct_token(user.id, &now);
+        let family: Id = ObjectId::new().into();
+        let token = self.construct_token(user.id, meta, family, &now);
         self.tokens_repo.insert(&token).await;
 
         Ok(token)
@@ -110,23 +142,54 @@ impl AuthServiceIf for AuthService {
             .await
             .ok_or_unauthorized()?;
 
+        let id = token.id.clone().ok_or_unauthorized()?;
+        let family = token.family_id().ok_or_unauthorized()?;
+
+        if token.rotated {
+            return Err(self.revoke_reused_family(&token, &family).await);
+        }
+
         if &token.refresh_lifetime < &now {
             return Err(AppError::unauthorized());
         }
 
-        let token = self.construct_token(token.user_id, &now);
+        // пару уже обновили параллельным запросом
+        if !self.tokens_repo.mark_rotated(&id).await {
+            return Err(self.revoke_reused_family(&token, &family).await);
+        }
+
+        // refreshed pair belongs to the same client
+        let meta = SessionMeta {
+            user_agent: token.user_agent,
+            ip: token.ip,
+        };
+        let token = self.construct_token(token.user_id, meta, family, &now);
         self.tokens_repo.insert(&token).await;
 
         Ok(token)
     }
 
     async fn validate_access(&self, access: &str, now: DateTime<Utc>) -> AppResult<User> {
+        self.validate_access_with_expiry(access, now)
+            .await
+            .map(|(user, _)| user)
+    }
+
+    async fn validate_access_with_expiry(
+        &self,
+        access: &str,
+        now: DateTime<Utc>,
+    ) -> AppResult<(User, DateTime<Utc>)> {
         let token = self
             .tokens_repo
             .find_by_access(access)
             .await
             .ok_or_unauthorized()?;
 
+        if token.rotated {
+            return Err(AppError::unauthorized());
+        }
+
         if &token.access_lifetime < &now && &token.refresh_lifetime <= &now {
             return Err(AppError::unauthorized());
         }
@@ -135,14 +198,119 @@ impl AuthServiceIf for AuthService {
             return Err(AppError::access_expire());
         }
 
-        self.find_user_by_access(&token.access)
+        let user = self
+            .users_repo
+            .find(&token.user_id)
             .await
-            .ok_or_unauthorized()
+            .ok_or_unauthorized()?;
+
+        Ok((user, token.access_lifetime))
+    }
+
+    async fn logout(&self, access: &str) -> AppResult<()> {
+        let token = self
+            .tokens_repo
+            .find_by_access(access)
+            .await
+            .filter(|t| !t.rotated)
+            .ok_or_unauthorized()?;
+        let family = token.family_id().ok_or_unauthorized()?;
+
+        self.tokens_repo.delete_family(&family).await;
+        self.sessions_channel
+            .publish(&token.user_id, SessionEvent::Revoked);
+
+        Ok(())
+    }
+
+    async fn logout_everywhere(&self, user: &User) -> AppResult<()> {
+        self.tokens_repo.delete_by_user_id(&user.id).await;
+        self.sessions_channel
+            .publish(&user.id, SessionEvent::Revoked);
+
+        Ok(())
+    }
+
+    async fn sessions(
+        &self,
+        user: &User,
+        current_access: &str,
+        now: DateTime<Utc>,
+    ) -> Vec<Session> {
+        let mut tokens: Vec<TokenPair> = self
+            .tokens_repo
+            .find_by_user_id(&user.id)
+            .await
+            .into_iter()
+            .filter(|t| !t.rotated && t.refresh_lifetime > now)
+            .collect();
+        tokens.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        // stored pairs have no raw tokens to compare with
+        let current_id = self
+            .tokens_repo
+            .find_by_access(current_access)
+            .await
+            .and_then(|t| t.id);
+
+        tokens
+            .into_iter()
+            .filter_map(|t| {
+                let id = t.id?;
+                Some(Session {
+                    current: Some(&id) == current_id.as_ref(),
+                    id,
+                    created_at: t.created_at,
+                    refresh_lifetime: t.refresh_lifetime,
+                    user_agent: t.user_agent,
+                    ip: t.ip,
+                })
+            })
+            .collect()
+    }
+
+    async fn revoke_session(&self, user: &User, id: &Id) -> AppResult<()> {
+        let family = self
+            .tokens_repo
+            .find_by_id_and_user_id(id, &user.id)
+            .await
+            .filter(|t| !t.rotated)
+            .and_then(|t| t.family_id())
+            .ok_or(AppError::not_found("Session not found"))?;
+
+        self.tokens_repo.delete_family(&family).await;
+        self.sessions_channel
+            .publish(&user.id, SessionEvent::Revoked);
+
+        Ok(())
     }
 }
 
 impl AuthService {
-    fn construct_token(&self, user_id: Id, current_time: &DateTime<Utc>) -> TokenPair {
+    ///
+    /// Refresh token уже обновлённой пары мог быть украден,
+    /// поэтому отзываем всю сессию и у вора, и у владельца
+    ///
+    async fn revoke_reused_family(&self, token: &TokenPair, family: &Id) -> AppError {
+        slog_warn!(
+            self.logger(),
+            "Refresh token reuse detected, session {} of user {} revoked",
+            family,
+            token.user_id
+        );
+        self.tokens_repo.delete_family(family).await;
+        self.sessions_channel
+            .publish(&token.user_id, SessionEvent::Revoked);
+
+        AppError::unauthorized()
+    }
+
+    fn construct_token(
+        &self,
+        user_id: Id,
+        meta: SessionMeta,
+        family: Id,
+        current_time: &DateTime<Utc>,
+    ) -> TokenPair {
         let current_time = current_time.to_owned();
 
         let access_lifetime =
@@ -151,12 +319,17 @@ impl AuthService {
             current_time + Duration::seconds(self.refresh_token_lifetime.num_seconds());
 
         let token = TokenPair {
+            id: None,
             access: Uuid::new_v4().to_string().replace("-", ""),
             refresh: Uuid::new_v4().to_string().replace("-", ""),
             access_lifetime,
             refresh_lifetime,
             created_at: current_time,
             user_id: user_id.into(),
+            user_agent: meta.user_agent,
+            ip: meta.ip,
+            family: Some(family),
+            rotated: false,
         };
         token
     }
@@ -1,23 +1,26 @@
+use crate::channels::groups::{GroupEvent, GroupsChannelIf, SetKey};
 use crate::errors::AppError;
 use crate::handlers::groups::{RemovedGroup, UserGroup, UserSet};
 use crate::handlers::Paging;
 use crate::logger::AppLoggerIf;
 use crate::repos::default_group_sets::{DefaultGroupSetsRepoIf, InsertDefaultGroupSetItem};
-use crate::repos::group_sets::{GroupSetItem, GroupSetsRepoIf, InsertGroupSetItem};
+use crate::repos::group_items::GroupItemsRepoIf;
+use crate::repos::group_sets::{GroupSetsRepoIf, InsertGroupSetItem};
 use crate::repos::groups::{Group, GroupsRepoIf, InsertGroup};
-use crate::repos::groups_ordering::GroupsOrderingRepoIf;
-use crate::repos::recent_sets::{InsertRecentSet, RecentSet, RecentSetsRepoIf};
-use crate::repos::sets::{InsertSet, SetsRepoIf};
+use crate::repos::recent_sets::RecentSetsRepoIf;
+use crate::repos::sets::{InsertSet, SetsRepoIf, SetsSortBy};
 use crate::repos::users::User;
 use crate::repos::Id;
 use crate::services::groups::IntoSet::{Default, Named};
+use crate::services::ordering::{key_between, spread_keys, MAX_ORDER_KEY_LEN};
 use crate::services::{PageInfo, Paged};
 use crate::utils::{AppResult, Refs};
-use async_graphql::SimpleObject;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use proc_macro::HasLogger;
 use shaku::{Component, Interface};
 use slog::Logger;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 pub const PAGING_MAX_LIMIT: i32 = 1000;
@@ -36,46 +39,84 @@ impl From<Option<String>> for IntoSet {
     }
 }
 
-#[derive(SimpleObject)]
-pub struct Set {
-    pub id: Id,
-    pub name: String,
-}
-
-fn recount_default_ordering(group_set: &mut Vec<InsertDefaultGroupSetItem>) {
-    for (i, set_item) in group_set.iter_mut().enumerate() {
-        set_item.order = i as i32
-    }
-}
-
-fn recount_ordering(group_set: &mut Vec<InsertGroupSetItem>) {
-    for (i, set_item) in group_set.iter_mut().enumerate() {
-        set_item.order = i as i32
-    }
-}
-
 #[async_trait]
 pub trait GroupsServiceIf: Interface {
-    async fn create_set(&self, user: User, name: String) -> AppResult<UserSet>;
+    async fn create_set(&self, user: User, name: String, now: DateTime<Utc>) -> AppResult<UserSet>;
 
     async fn recent_sets(&self, user: User) -> Vec<UserSet>;
 
-    // async fn create_group(
-    //     &self,
-    //     user: User,
-    //     name: String,
-    //     set: IntoSet,
-    //     after_group: Option<Id>,
-    // ) -> AppResult<UserGroup>;
-    //
-    // async fn remove_group(&self, user: User, id: Id) -> AppResult<RemovedGroup>;
-    //
-    // async fn list_groups(
-    //     &self,
-    //     user: User,
-    //     set: IntoSet,
-    //     paging: Option<Paging>,
-    // ) -> AppResult<Paged<UserGroup>>;
+    /// All sets created by user
+    async fn list_sets(
+        &self,
+        user: User,
+        paging: Option<Paging>,
+        sort_by: SetsSortBy,
+        name_contains: Option<String>,
+    ) -> AppResult<Paged<UserSet>>;
+
+    /// Переименовывает сет вместе с копиями имени в `group_sets`
+    async fn rename_set(
+        &self,
+        user: User,
+        id: Id,
+        name: String,
+        now: DateTime<Utc>,
+    ) -> AppResult<UserSet>;
+
+    ///
+    /// Удаляет сет и убирает его из недавних. Группы сета не удаляются,
+    /// они остаются в дефолтном сете
+    ///
+    async fn delete_set(&self, user: User, id: Id) -> AppResult<UserSet>;
+
+    async fn create_group(
+        &self,
+        user: User,
+        name: String,
+        set: IntoSet,
+        after_group: Option<Id>,
+        now: DateTime<Utc>,
+    ) -> AppResult<UserGroup>;
+
+    async fn remove_group(&self, user: User, id: Id, now: DateTime<Utc>)
+        -> AppResult<RemovedGroup>;
+
+    ///
+    /// Переименовывает группу вместе с копиями имени в сетах, в которых она лежит,
+    /// и рассылает изменение подписчикам этих сетов. Возвращает группу как она в дефолтном сете.
+    /// Транзакций в драйвере нет, поэтому сначала переименовывается сама группа, потом копии,
+    /// повторное переименование тем же именем догоняет не обновившиеся копии
+    ///
+    async fn rename_group(
+        &self,
+        user: User,
+        id: Id,
+        name: String,
+        now: DateTime<Utc>,
+    ) -> AppResult<UserGroup>;
+
+    ///
+    /// Перемещает группу в сете сразу после `after_group`, в начало сета если `None`.
+    /// Если группа лежит в другом именованном сете, она переносится в `set`,
+    /// если ни в каком - добавляется в него. В дефолтном сете лежат все группы,
+    /// так что для него меняется только порядок
+    ///
+    async fn move_group(
+        &self,
+        user: User,
+        id: Id,
+        set: IntoSet,
+        after_group: Option<Id>,
+        now: DateTime<Utc>,
+    ) -> AppResult<UserGroup>;
+
+    async fn list_groups(
+        &self,
+        user: User,
+        set: IntoSet,
+        paging: Option<Paging>,
+        now: DateTime<Utc>,
+    ) -> AppResult<Paged<UserGroup>>;
 }
 
 #[derive(Component, HasLogger)]
@@ -97,16 +138,22 @@ pub struct GroupsService {
     groups_repo: Arc<dyn GroupsRepoIf>,
 
     #[shaku(inject)]
-    groups_ordering_repo: Arc<dyn GroupsOrderingRepoIf>,
+    group_items_repo: Arc<dyn GroupItemsRepoIf>,
+
+    #[shaku(inject)]
+    groups_channel: Arc<dyn GroupsChannelIf>,
 
     #[logger]
     #[shaku(inject)]
     app_logger: Arc<dyn AppLoggerIf>,
+
+    #[shaku(no_default)]
+    recent_sets_max: u32,
 }
 
 #[async_trait]
 impl GroupsServiceIf for GroupsService {
-    async fn create_set(&self, user: User, name: String) -> AppResult<UserSet> {
+    async fn create_set(&self, user: User, name: String, now: DateTime<Utc>) -> AppResult<UserSet> {
         if let Some(_) = self
             .sets_repo
             .find_one_by_creator_id_and_name(&user.id, &name)
@@ -123,14 +170,7 @@ impl GroupsServiceIf for GroupsService {
             })
             .await;
 
-        self.update_recents(
-            &user,
-            &Set {
-                id: set.id.clone(),
-                name: set.name.clone(),
-            },
-        )
-        .await;
+        self.update_recents(&user, &set.id, now).await;
 
         Ok(UserSet {
             id: set.id,
@@ -145,12 +185,21 @@ impl GroupsServiceIf for GroupsService {
             .await
             .into_iter()
             .map(|x| x.set_id)
+            .take(self.recent_sets_max as usize)
             .collect();
 
-        self.sets_repo
+        // sets come in arbitrary order, so they are put back in the recents order
+        let mut sets: HashMap<Id, crate::repos::sets::Set> = self
+            .sets_repo
             .find_many(recents_ids.refs())
             .await
             .into_iter()
+            .map(|s| (s.id.clone(), s))
+            .collect();
+
+        recents_ids
+            .iter()
+            .filter_map(|id| sets.remove(id))
             .map(|s| UserSet {
                 id: s.id,
                 name: s.name,
@@ -158,301 +207,671 @@ impl GroupsServiceIf for GroupsService {
             .collect()
     }
 
-    // async fn create_group(
-    //     &self,
-    //     user: User,
-    //     name: String,
-    //     set: IntoSet,
-    //     after_group: Option<Id>,
-    // ) -> AppResult<UserGroup> {
-    //     if let Named(set_name) = &set {
-    //         if let Some(_) = self
-    //             .group_sets_repo
-    //             .find_by_user_id_set_name_and_group_name(&user.id, &set_name, &name)
-    //             .await
-    //         {
-    //             return Err(AppError::validation(&format!(
-    //                 "Group `{}` already exists",
-    //                 name
-    //             )));
-    //         }
-    //     }
-    //
-    //     let group_entity = self
-    //         .groups_repo
-    //         .insert(InsertGroup {
-    //             creator_id: (&user.id).clone(),
-    //             name: name.to_string(),
-    //             removed: false,
-    //         })
-    //         .await;
-    //
-    //     let mut inserted_group_position = self
-    //         .insert_group_into_default_set(&user, &group_entity, &after_group)
-    //         .await;
-    //
-    //     let default_set_item = self
-    //         .default_group_sets_repo
-    //         .find_by_group_id(&group_entity.id)
-    //         .await
-    //         .unwrap();
-    //
-    //     let mut set_item: Option<GroupSetItem> = None;
-    //     if let Named(name) = &set {
-    //         inserted_group_position = self
-    //             .insert_group_into_set(&user, &group_entity, name, &after_group)
-    //             .await;
-    //
-    //         set_item = self
-    //             .group_sets_repo
-    //             .find_by_group_id(&group_entity.id)
-    //             .await
-    //     }
-    //
-    //     match set_item {
-    //         Some(set_item) => Ok(UserGroup {
-    //             id: set_item.group_id,
-    //             id_in_set: set_item.id,
-    //             name: set_item.group_name,
-    //             order: set_item.order,
-    //         }),
-    //         None => Ok(UserGroup {
-    //             id: default_set_item.group_id,
-    //             id_in_set: default_set_item.id,
-    //             name: default_set_item.group_name,
-    //             order: default_set_item.order,
-    //         }),
-    //     }
-    // }
-
-    // async fn remove_group(&self, _user: User, _id: Id) -> AppResult<RemovedGroup> {
-    //     unimplemented!()
-
-    // let mut ordering = self.groups_ordering_repo.get_by_user_id(&user.id).await;
-    //
-    // let remove_group_position =
-    //     ordering
-    //         .iter()
-    //         .position(|g| &g.group_id == id)
-    //         .ok_or(AppError::validation(&format!(
-    //             "Group `{}` you are trying to remove not exists",
-    //             id
-    //         )))?;
-    //
-    // let removed_order = ordering.remove(remove_group_position);
-    // self.groups_repo.mark_removed(&removed_order.group_id).await;
-    //
-    // let mut new_ordering: Vec<InsertGroupOrder> = ordering
-    //     .iter()
-    //     .map(|o| InsertGroupOrder {
-    //         user_id: o.user_id.clone(),
-    //         group_id: o.group_id.clone(),
-    //         order: o.order,
-    //     })
-    //     .collect();
-    //
-    // recount_default_ordering(&mut new_ordering);
-    // self.groups_ordering_repo.delete_by_user_id(&user.id).await;
-    // self.groups_ordering_repo.insert(new_ordering).await;
-    //
-    // let group_entity = self
-    //     .groups_repo
-    //     .find(&removed_order.group_id)
-    //     .await
-    //     .unwrap();
-    //
-    // Ok(RemovedGroup {
-    //     id: removed_order.group_id,
-    //     name: group_entity.name,
-    // })
-    // }
-
-    // async fn list_groups(
-    //     &self,
-    //     user: User,
-    //     set: IntoSet,
-    //     paging: Option<Paging>,
-    // ) -> AppResult<Paged<UserGroup>> {
-    //     let paging: crate::services::Paging = paging.into();
-    //
-    //     if paging.limit > PAGING_MAX_LIMIT {
-    //         return Err(AppError::validation(&format!(
-    //             "Paging limit can not be more then {}",
-    //             PAGING_MAX_LIMIT
-    //         )));
-    //     }
-    //
-    //     let user_groups: Vec<UserGroup> = match &set {
-    //         Named(name) => self
-    //             .group_sets_repo
-    //             .get_paged_by_user_id_and_set_name(&user.id, name, paging.offset, paging.limit)
-    //             .await
-    //             .into_iter()
-    //             .map(|x| UserGroup {
-    //                 id: x.group_id,
-    //                 id_in_set: x.id,
-    //                 name: x.group_name,
-    //                 order: x.order,
-    //             })
-    //             .collect(),
-    //
-    //         Default => self
-    //             .default_group_sets_repo
-    //             .get_paged_by_user_id(&user.id, paging.offset, paging.limit)
-    //             .await
-    //             .into_iter()
-    //             .map(|x| UserGroup {
-    //                 id: x.group_id,
-    //                 id_in_set: x.id,
-    //                 name: x.group_name,
-    //                 order: x.order,
-    //             })
-    //             .collect(),
-    //     };
-    //
-    //     Ok(Paged {
-    //         objects: user_groups,
-    //         page_info: PageInfo {
-    //             offset: paging.offset,
-    //             limit: paging.limit,
-    //             total: None,
-    //         },
-    //     })
-    // }
-}
+    async fn list_sets(
+        &self,
+        user: User,
+        paging: Option<Paging>,
+        sort_by: SetsSortBy,
+        name_contains: Option<String>,
+    ) -> AppResult<Paged<UserSet>> {
+        let paging: crate::services::Paging = paging.into();
+
+        if paging.limit > PAGING_MAX_LIMIT {
+            return Err(AppError::validation(&format!(
+                "Paging limit can not be more then {}",
+                PAGING_MAX_LIMIT
+            )));
+        }
 
-impl GroupsService {
-    async fn update_recents(&self, user: &User, set: &Set) {
-        let mut recents = self.recent_sets_repo.find_by_user_id(&user.id).await;
-        self.recent_sets_repo
-            .delete_many(recents.iter().map(|r| &r.id).collect())
+        let name_contains = name_contains.as_deref().filter(|n| !n.trim().is_empty());
+        let sets = self
+            .sets_repo
+            .get_paged_by_creator_id(
+                &user.id,
+                name_contains,
+                sort_by,
+                paging.offset,
+                paging.limit,
+            )
+            .await;
+        let total = self
+            .sets_repo
+            .count_by_creator_id(&user.id, name_contains)
             .await;
 
-        match recents
-            .iter()
-            .position(|recent_set| recent_set.id == set.id)
+        Ok(Paged {
+            objects: sets
+                .into_iter()
+                .map(|s| UserSet {
+                    id: s.id,
+                    name: s.name,
+                })
+                .collect(),
+            page_info: PageInfo {
+                offset: paging.offset,
+                limit: paging.limit,
+                total: Some(total as i32),
+            },
+        })
+    }
+
+    async fn rename_set(
+        &self,
+        user: User,
+        id: Id,
+        name: String,
+        now: DateTime<Utc>,
+    ) -> AppResult<UserSet> {
+        let set = self
+            .find_user_set(&user, &id)
+            .await
+            .ok_or(AppError::not_found(
+                "Set you are trying to rename not exists",
+            ))?;
+
+        if set.name == name {
+            return Ok(UserSet {
+                id: set.id,
+                name: set.name,
+            });
+        }
+        if let Some(_) = self
+            .sets_repo
+            .find_one_by_creator_id_and_name(&user.id, &name)
+            .await
+        {
+            return Err(AppError::validation("set with same name exists"));
+        }
+        // группы кладутся в сет по имени и без создания сета,
+        // переименование в такое имя смешало бы два сета в один
+        if self
+            .group_sets_repo
+            .count_by_user_id_and_set_name(&user.id, &name)
+            .await
+            > 0
         {
-            Some(position) => {
-                recents.remove(position);
+            return Err(AppError::validation("set with same name exists"));
+        }
+
+        self.sets_repo.rename(&set.id, &name).await;
+        self.group_sets_repo
+            .rename_set(&user.id, &set.name, &name)
+            .await;
+        self.update_recents(&user, &set.id, now).await;
+
+        Ok(UserSet { id: set.id, name })
+    }
+
+    async fn delete_set(&self, user: User, id: Id) -> AppResult<UserSet> {
+        let set = self
+            .find_user_set(&user, &id)
+            .await
+            .ok_or(AppError::not_found(
+                "Set you are trying to delete not exists",
+            ))?;
+
+        self.group_sets_repo
+            .remove_by_user_id_and_set_name(&user.id, &set.name)
+            .await;
+        self.recent_sets_repo.delete_by_set_id(&set.id).await;
+        self.sets_repo.delete(&set.id).await;
+
+        Ok(UserSet {
+            id: set.id,
+            name: set.name,
+        })
+    }
+
+    async fn create_group(
+        &self,
+        user: User,
+        name: String,
+        set: IntoSet,
+        after_group: Option<Id>,
+        now: DateTime<Utc>,
+    ) -> AppResult<UserGroup> {
+        self.check_group_name_free(&user, &set, &name, None).await?;
+
+        let group_entity = self
+            .groups_repo
+            .insert(InsertGroup {
+                creator_id: (&user.id).clone(),
+                name: name.to_string(),
+                removed: false,
+            })
+            .await;
+
+        let (default_set_item_id, default_order_key) = self
+            .insert_group_into_default_set(&user, &group_entity, &after_group)
+            .await;
+
+        match &set {
+            Named(set_name) => {
+                let (set_item_id, order_key) = self
+                    .insert_group_into_set(&user, &group_entity, set_name, &after_group)
+                    .await;
+                self.update_recents_by_set_name(&user, set_name, now).await;
+
+                Ok(UserGroup {
+                    id: group_entity.id,
+                    id_in_set: set_item_id,
+                    name: group_entity.name,
+                    order: self
+                        .group_sets_repo
+                        .count_before(&user.id, set_name, &order_key)
+                        .await as i32,
+                })
+            }
+            Default => Ok(UserGroup {
+                id: group_entity.id,
+                id_in_set: default_set_item_id,
+                name: group_entity.name,
+                order: self
+                    .default_group_sets_repo
+                    .count_before(&user.id, &default_order_key)
+                    .await as i32,
+            }),
+        }
+    }
+
+    async fn remove_group(
+        &self,
+        user: User,
+        id: Id,
+        now: DateTime<Utc>,
+    ) -> AppResult<RemovedGroup> {
+        let group: Group = self
+            .groups_repo
+            .find(&id)
+            .await
+            .filter(|g| g.creator_id == user.id && !g.removed)
+            .ok_or(AppError::validation(&format!(
+                "Group `{}` you are trying to remove not exists",
+                id
+            )))?;
+        let set_item = self.group_sets_repo.find_by_group_id(&group.id).await;
+
+        self.groups_repo.mark_removed(&group.id).await;
+        self.default_group_sets_repo
+            .remove_by_group_id(&group.id)
+            .await;
+        self.group_sets_repo.remove_by_group_id(&group.id).await;
+        self.group_items_repo.delete_by_group_id(&group.id).await;
+        if let Some(item) = set_item {
+            self.update_recents_by_set_name(&user, &item.set_name, now)
+                .await;
+        }
+
+        Ok(RemovedGroup {
+            id: group.id,
+            name: group.name,
+        })
+    }
+
+    async fn rename_group(
+        &self,
+        user: User,
+        id: Id,
+        name: String,
+        now: DateTime<Utc>,
+    ) -> AppResult<UserGroup> {
+        let group: Group = self
+            .groups_repo
+            .find(&id)
+            .await
+            .filter(|g| g.creator_id == user.id && !g.removed)
+            .ok_or(AppError::validation(&format!(
+                "Group `{}` you are trying to rename not exists",
+                id
+            )))?;
+        let set_item = self.group_sets_repo.find_by_group_id(&group.id).await;
+
+        // имя должно быть свободно в каждом сете где лежит группа
+        self.check_group_name_free(&user, &Default, &name, Some(&group.id))
+            .await?;
+        if let Some(item) = &set_item {
+            let set = Named(item.set_name.clone());
+            self.check_group_name_free(&user, &set, &name, Some(&group.id))
+                .await?;
+        }
+
+        self.groups_repo.rename(&group.id, &name).await;
+        self.default_group_sets_repo
+            .rename_group(&group.id, &name)
+            .await;
+        self.group_sets_repo.rename_group(&group.id, &name).await;
+
+        if let Some(item) = set_item {
+            self.update_recents_by_set_name(&user, &item.set_name, now)
+                .await;
+            let in_set = UserGroup {
+                id: group.id.clone(),
+                id_in_set: item.id,
+                name: name.clone(),
+                order: self
+                    .group_sets_repo
+                    .count_before(&user.id, &item.set_name, &item.order_key)
+                    .await as i32,
+            };
+            self.groups_channel.publish(
+                &SetKey {
+                    user_id: user.id.clone(),
+                    set_name: Some(item.set_name),
+                },
+                GroupEvent::Updated(in_set),
+            );
+        }
+
+        let default_item = self
+            .default_group_sets_repo
+            .find_by_group_id(&group.id)
+            .await
+            .ok_or(AppError::internal())?;
+        let in_default_set = UserGroup {
+            id: group.id,
+            id_in_set: default_item.id,
+            name,
+            order: self
+                .default_group_sets_repo
+                .count_before(&user.id, &default_item.order_key)
+                .await as i32,
+        };
+        self.groups_channel.publish(
+            &SetKey {
+                user_id: user.id,
+                set_name: None,
+            },
+            GroupEvent::Updated(in_default_set.clone()),
+        );
+
+        Ok(in_default_set)
+    }
+
+    async fn move_group(
+        &self,
+        user: User,
+        id: Id,
+        set: IntoSet,
+        after_group: Option<Id>,
+        now: DateTime<Utc>,
+    ) -> AppResult<UserGroup> {
+        let group: Group = self
+            .groups_repo
+            .find(&id)
+            .await
+            .filter(|g| g.creator_id == user.id && !g.removed)
+            .ok_or(AppError::validation(&format!(
+                "Group `{}` you are trying to move not exists",
+                id
+            )))?;
+
+        match &set {
+            Named(set_name) => {
+                let set_item = self
+                    .group_sets_repo
+                    .find_by_group_id(&group.id)
+                    .await
+                    .filter(|x| x.user_id == user.id);
+
+                let entering_set = set_item.as_ref().map_or(true, |x| &x.set_name != set_name);
+                if entering_set {
+                    if let Some(_) = self
+                        .group_sets_repo
+                        .find_by_user_id_set_name_and_group_name(&user.id, set_name, &group.name)
+                        .await
+                    {
+                        return Err(AppError::validation(&format!(
+                            "Group `{}` already exists",
+                            group.name
+                        )));
+                    }
+                }
+
+                let order_key = self
+                    .new_order_key_in_set(&user, set_name, &after_group)
+                    .await;
+
+                let id_in_set = match set_item {
+                    Some(set_item) if !entering_set => {
+                        self.group_sets_repo
+                            .set_order_key(&set_item.id, &order_key)
+                            .await;
+                        set_item.id
+                    }
+                    // сет и ключ меняются одним апдейтом, чтобы группа не оказалась
+                    // в новом сете со старым ключом
+                    Some(set_item) => {
+                        self.group_sets_repo
+                            .move_to_set(&set_item.id, set_name, &order_key)
+                            .await;
+                        set_item.id
+                    }
+                    None => {
+                        self.group_sets_repo
+                            .insert(&InsertGroupSetItem {
+                                user_id: user.id.clone(),
+                                group_id: group.id.clone(),
+                                group_name: group.name.clone(),
+                                set_name: set_name.to_string(),
+                                order_key: order_key.clone(),
+                            })
+                            .await
+                    }
+                };
+                self.update_recents_by_set_name(&user, set_name, now).await;
+
+                Ok(UserGroup {
+                    id: group.id,
+                    id_in_set,
+                    name: group.name,
+                    order: self
+                        .group_sets_repo
+                        .count_before(&user.id, set_name, &order_key)
+                        .await as i32,
+                })
             }
-            None => (),
+            Default => {
+                let set_item = self
+                    .default_group_sets_repo
+                    .find_by_group_id(&group.id)
+                    .await
+                    .ok_or(AppError::internal())?;
+
+                let order_key = self.new_order_key_in_default_set(&user, &after_group).await;
+                self.default_group_sets_repo
+                    .set_order_key(&set_item.id, &order_key)
+                    .await;
+
+                Ok(UserGroup {
+                    id: set_item.group_id,
+                    id_in_set: set_item.id,
+                    name: set_item.group_name,
+                    order: self
+                        .default_group_sets_repo
+                        .count_before(&user.id, &order_key)
+                        .await as i32,
+                })
+            }
+        }
+    }
+
+    async fn list_groups(
+        &self,
+        user: User,
+        set: IntoSet,
+        paging: Option<Paging>,
+        now: DateTime<Utc>,
+    ) -> AppResult<Paged<UserGroup>> {
+        let paging: crate::services::Paging = paging.into();
+
+        if paging.limit > PAGING_MAX_LIMIT {
+            return Err(AppError::validation(&format!(
+                "Paging limit can not be more then {}",
+                PAGING_MAX_LIMIT
+            )));
+        }
+
+        if let Named(name) = &set {
+            self.update_recents_by_set_name(&user, name, now).await;
+        }
+
+        let (user_groups, total): (Vec<UserGroup>, i64) = match &set {
+            Named(name) => (
+                self.group_sets_repo
+                    .get_paged_by_user_id_and_set_name(&user.id, name, paging.offset, paging.limit)
+                    .await
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, x)| UserGroup {
+                        id: x.group_id,
+                        id_in_set: x.id,
+                        name: x.group_name,
+                        order: paging.offset + i as i32,
+                    })
+                    .collect(),
+                self.group_sets_repo
+                    .count_by_user_id_and_set_name(&user.id, name)
+                    .await,
+            ),
+
+            Default => (
+                self.default_group_sets_repo
+                    .get_paged_by_user_id(&user.id, paging.offset, paging.limit)
+                    .await
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, x)| UserGroup {
+                        id: x.group_id,
+                        id_in_set: x.id,
+                        name: x.group_name,
+                        order: paging.offset + i as i32,
+                    })
+                    .collect(),
+                self.default_group_sets_repo
+                    .count_by_user_id(&user.id)
+                    .await,
+            ),
         };
 
-        let mut recents_to_insert: Vec<InsertRecentSet> = recents
-            .into_iter()
-            .map(|r| InsertRecentSet {
-                user_id: r.user_id,
-                set_id: r.set_id,
+        Ok(Paged {
+            objects: user_groups,
+            page_info: PageInfo {
+                offset: paging.offset,
+                limit: paging.limit,
+                total: Some(total as i32),
+            },
+        })
+    }
+}
+
+impl GroupsService {
+    /// Validation error if there is another group named `name` in the set,
+    /// `group_id` is the group being renamed, it does not conflict with itself
+    async fn check_group_name_free(
+        &self,
+        user: &User,
+        set: &IntoSet,
+        name: &str,
+        group_id: Option<&Id>,
+    ) -> AppResult<()> {
+        let other_group_id = match set {
+            Named(set_name) => self
+                .group_sets_repo
+                .find_by_user_id_set_name_and_group_name(&user.id, set_name, name)
+                .await
+                .map(|i| i.group_id),
+            Default => self
+                .default_group_sets_repo
+                .find_by_user_id_and_group_name(&user.id, name)
+                .await
+                .map(|i| i.group_id),
+        };
+
+        match other_group_id {
+            Some(other) if Some(&other) != group_id => Err(AppError::validation(&format!(
+                "Group `{}` already exists",
+                name
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    async fn find_user_set(&self, user: &User, id: &Id) -> Option<crate::repos::sets::Set> {
+        self.sets_repo
+            .find(id)
+            .await
+            .filter(|set| set.creator_id == user.id)
+    }
+
+    ///
+    /// Отмечает сет использованным сейчас и убирает из недавних
+    /// всё что не влезает в `recent_sets_max`
+    ///
+    async fn update_recents(&self, user: &User, set_id: &Id, now: DateTime<Utc>) {
+        self.recent_sets_repo.touch(&user.id, set_id, now).await;
+
+        let recents = self.recent_sets_repo.find_by_user_id(&user.id).await;
+        if recents.len() > self.recent_sets_max as usize {
+            self.recent_sets_repo
+                .delete_many(
+                    recents
+                        .iter()
+                        .skip(self.recent_sets_max as usize)
+                        .map(|r| &r.id)
+                        .collect(),
+                )
+                .await;
+        }
+    }
+
+    /// Named sets do not have to be created before groups are put into them,
+    /// only created ones get into recents
+    async fn update_recents_by_set_name(&self, user: &User, set_name: &str, now: DateTime<Utc>) {
+        if let Some(set) = self
+            .sets_repo
+            .find_one_by_creator_id_and_name(&user.id, set_name)
+            .await
+        {
+            self.update_recents(user, &set.id, now).await;
+        }
+    }
+
+    /// Returns id of the group in set and its order key
+    async fn insert_group_into_set(
+        &self,
+        user: &User,
+        group: &Group,
+        set_name: &str,
+        after_group: &Option<Id>,
+    ) -> (Id, String) {
+        let order_key = self.new_order_key_in_set(user, set_name, after_group).await;
+
+        let id = self
+            .group_sets_repo
+            .insert(&InsertGroupSetItem {
+                user_id: user.id.clone(),
+                group_id: group.id.clone(),
+                group_name: (&group.name).clone(),
+                set_name: set_name.to_string(),
+                order_key: order_key.clone(),
             })
-            .collect();
+            .await;
+
+        (id, order_key)
+    }
 
-        recents_to_insert.insert(
-            0,
-            InsertRecentSet {
+    /// Returns id of the group in default set and its order key
+    async fn insert_group_into_default_set(
+        &self,
+        user: &User,
+        group: &Group,
+        after_group: &Option<Id>,
+    ) -> (Id, String) {
+        let order_key = self.new_order_key_in_default_set(user, after_group).await;
+
+        let id = self
+            .default_group_sets_repo
+            .insert(&InsertDefaultGroupSetItem {
                 user_id: user.id.clone(),
-                set_id: set.id.clone(),
-            },
-        );
+                group_id: group.id.clone(),
+                group_name: (&group.name).clone(),
+                order_key: order_key.clone(),
+            })
+            .await;
+
+        (id, order_key)
+    }
+
+    ///
+    /// Ключ для группы, вставляемой в сет после `after_group` (в начало если `None`
+    /// или если `after_group` нет в сете). Другие группы сета не трогаются,
+    /// кроме случая когда ключи стали слишком длинными и сет перебалансируется
+    ///
+    async fn new_order_key_in_set(
+        &self,
+        user: &User,
+        set_name: &str,
+        after_group: &Option<Id>,
+    ) -> String {
+        let order_key = self
+            .key_after_group_in_set(user, set_name, after_group)
+            .await;
+        if order_key.len() <= MAX_ORDER_KEY_LEN {
+            return order_key;
+        }
 
-        self.recent_sets_repo
-            .insert_many(recents_to_insert.refs())
+        let group_set = self
+            .group_sets_repo
+            .get_by_user_id_and_set_name(&user.id, set_name)
             .await;
+        for (item, key) in group_set.iter().zip(spread_keys(group_set.len())) {
+            self.group_sets_repo.set_order_key(&item.id, &key).await;
+        }
+
+        self.key_after_group_in_set(user, set_name, after_group)
+            .await
+    }
+
+    async fn key_after_group_in_set(
+        &self,
+        user: &User,
+        set_name: &str,
+        after_group: &Option<Id>,
+    ) -> String {
+        let prev_key = match after_group {
+            Some(id) => self
+                .group_sets_repo
+                .find_by_set_name_and_group_id(&user.id, set_name, id)
+                .await
+                .map(|x| x.order_key),
+            None => None,
+        };
+        let next_key = self
+            .group_sets_repo
+            .find_first_after(&user.id, set_name, prev_key.as_deref())
+            .await
+            .map(|x| x.order_key);
+
+        key_between(prev_key.as_deref(), next_key.as_deref())
+    }
+
+    /// Same as `new_order_key_in_set` for default set
+    async fn new_order_key_in_default_set(&self, user: &User, after_group: &Option<Id>) -> String {
+        let order_key = self.key_after_group_in_default_set(user, after_group).await;
+        if order_key.len() <= MAX_ORDER_KEY_LEN {
+            return order_key;
+        }
+
+        let default_set = self.default_group_sets_repo.get_by_user_id(&user.id).await;
+        for (item, key) in default_set.iter().zip(spread_keys(default_set.len())) {
+            self.default_group_sets_repo
+                .set_order_key(&item.id, &key)
+                .await;
+        }
+
+        self.key_after_group_in_default_set(user, after_group).await
     }
 
-    // async fn insert_group_into_set(
-    //     &self,
-    //     user: &User,
-    //     group: &Group,
-    //     set_name: &str,
-    //     after_group: &Option<Id>,
-    // ) {
-    //     let mut group_set: Vec<InsertGroupSetItem> = self
-    //         .group_sets_repo
-    //         .get_by_user_id_and_set_name(&user.id, set_name)
-    //         .await
-    //         .iter()
-    //         .enumerate()
-    //         .map(|(i, group)| InsertGroupSetItem {
-    //             user_id: group.user_id.clone(),
-    //             group_id: group.group_id.clone(),
-    //             group_name: group.group_name.clone(),
-    //             set_name: set_name.to_string(),
-    //             order: i as i32,
-    //         })
-    //         .collect();
-    //
-    //     let new_group_position = match after_group {
-    //         None => 0,
-    //         Some(id) => match group_set.iter().position(|o| o.group_id == *id) {
-    //             Some(pos) => pos + 1,
-    //             None => 0,
-    //         },
-    //     };
-    //
-    //     group_set.insert(
-    //         new_group_position,
-    //         InsertGroupSetItem {
-    //             user_id: user.id.clone(),
-    //             group_id: group.id.clone(),
-    //             group_name: (&group.name).clone(),
-    //             set_name: set_name.to_string(),
-    //             order: new_group_position as i32,
-    //         },
-    //     );
-    //
-    //     recount_ordering(&mut group_set);
-    //
-    //     self.group_sets_repo
-    //         .remove_by_set_name_and_user_id(set_name, &user.id)
-    //         .await;
-    //     self.group_sets_repo.insert(group_set.refs()).await;
-    // }
-    //
-    // async fn insert_group_into_default_set(
-    //     &self,
-    //     user: &User,
-    //     group: &Group,
-    //     after_group: &Option<Id>,
-    // ) {
-    //     let mut default_set: Vec<InsertDefaultGroupSetItem> = self
-    //         .default_group_sets_repo
-    //         .find_by_user_id(&user.id)
-    //         .await
-    //         .iter()
-    //         .enumerate()
-    //         .map(|(i, group)| InsertDefaultGroupSetItem {
-    //             user_id: group.user_id.clone(),
-    //             group_id: group.group_id.clone(),
-    //             group_name: group.group_name.clone(),
-    //             order: i as i32,
-    //         })
-    //         .collect();
-    //
-    //     let new_group_position = match after_group {
-    //         None => 0,
-    //         Some(id) => match default_set.iter().position(|o| o.group_id == *id) {
-    //             Some(pos) => pos + 1,
-    //             None => 0,
-    //         },
-    //     };
-    //
-    //     default_set.insert(
-    //         new_group_position,
-    //         InsertDefaultGroupSetItem {
-    //             user_id: user.id.clone(),
-    //             group_id: group.id.clone(),
-    //             group_name: (&group.name).clone(),
-    //             order: new_group_position as i32,
-    //         },
-    //     );
-    //
-    //     recount_default_ordering(&mut default_set);
-    //
-    //     self.default_group_sets_repo
-    //         .remove_by_user_id(&user.id)
-    //         .await;
-    //     self.default_group_sets_repo
-    //         .insert(default_set.refs())
-    //         .await;
-    // }
+    async fn key_after_group_in_default_set(
+        &self,
+        user: &User,
+        after_group: &Option<Id>,
+    ) -> String {
+        let prev_key = match after_group {
+            Some(id) => self
+                .default_group_sets_repo
+                .find_by_group_id(id)
+                .await
+                .filter(|x| x.user_id == user.id)
+                .map(|x| x.order_key),
+            None => None,
+        };
+        let next_key = self
+            .default_group_sets_repo
+            .find_first_after(&user.id, prev_key.as_deref())
+            .await
+            .map(|x| x.order_key);
+
+        key_between(prev_key.as_deref(), next_key.as_deref())
+    }
 }
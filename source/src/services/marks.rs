@@ -0,0 +1,285 @@
+use std::cmp::max;
+
+use crate::errors::AppError;
+use crate::handlers::stack::ChangeMark;
+use crate::repos::marks::MarkKind;
+use crate::utils::AppResult;
+
+/// Unit text offsets are counted in
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OffsetUnit {
+    /// UTF-16 code units, as javascript strings are indexed
+    Utf16,
+    /// unicode scalar values, as rust `str::chars` iterates
+    Char,
+    /// UTF-8 bytes, as rust strings are indexed
+    Byte,
+}
+
+impl OffsetUnit {
+    fn char_len(self, c: char) -> usize {
+        match self {
+            OffsetUnit::Utf16 => c.len_utf16(),
+            OffsetUnit::Char => 1,
+            OffsetUnit::Byte => c.len_utf8(),
+        }
+    }
+}
+
+/// Marks `from`/`to` are stored and passed through the api in UTF-16 code units
+/// because browser clients count in them
+pub const MARK_OFFSET_UNIT: OffsetUnit = OffsetUnit::Utf16;
+
+/// Text length in the unit
+pub fn text_len(text: &str, unit: OffsetUnit) -> usize {
+    text.chars().map(|c| unit.char_len(c)).sum()
+}
+
+/// Length of characters in `MARK_OFFSET_UNIT`
+fn units(chars: &[char]) -> i32 {
+    chars
+        .iter()
+        .map(|c| MARK_OFFSET_UNIT.char_len(*c))
+        .sum::<usize>() as i32
+}
+
+/// Converts text offset from one unit to another.
+/// `None` if offset is out of text or points inside a character,
+/// e.g. between surrogate pair halves of an emoji
+pub fn convert_offset(
+    text: &str,
+    offset: usize,
+    from: OffsetUnit,
+    to: OffsetUnit,
+) -> Option<usize> {
+    let mut from_offset = 0;
+    let mut to_offset = 0;
+    for c in text.chars() {
+        if from_offset >= offset {
+            break;
+        }
+        from_offset += from.char_len(c);
+        to_offset += to.char_len(c);
+    }
+
+    if from_offset == offset {
+        Some(to_offset)
+    } else {
+        None
+    }
+}
+
+/// Checks marks lie within block text, do not split characters
+/// and have attributes their kind requires.
+/// `path` is a path to the marks input field, e.g. `blocks.0.marks`,
+/// errors are reported for the exact mark field like `blocks.0.marks.1.to`
+pub fn validate_marks(path: &str, text: &str, marks: &Vec<ChangeMark>) -> AppResult<()> {
+    let text_len = text_len(text, MARK_OFFSET_UNIT) as i32;
+    let is_char_boundary = |offset: i32| {
+        convert_offset(text, offset as usize, MARK_OFFSET_UNIT, OffsetUnit::Char).is_some()
+    };
+
+    for (i, mark) in marks.iter().enumerate() {
+        let field = |name: &str| format!("{}.{}.{}", path, i, name);
+
+        if mark.from < 0 || mark.from >= text_len {
+            return Err(AppError::field_validation(
+                &field("from"),
+                "Mark must start within block text",
+            ));
+        }
+        if mark.to > text_len {
+            return Err(AppError::field_validation(
+                &field("to"),
+                "Mark must end within block text",
+            ));
+        }
+        if mark.to <= mark.from {
+            return Err(AppError::field_validation(
+                &field("to"),
+                "Mark must end after it starts",
+            ));
+        }
+        if !is_char_boundary(mark.from) {
+            return Err(AppError::field_validation(
+                &field("from"),
+                "Mark must not start inside a character",
+            ));
+        }
+        if !is_char_boundary(mark.to) {
+            return Err(AppError::field_validation(
+                &field("to"),
+                "Mark must not end inside a character",
+            ));
+        }
+        let has_href = mark.href.as_ref().map_or(false, |h| !h.trim().is_empty());
+        if mark.kind == MarkKind::Link && !has_href {
+            return Err(AppError::field_validation(
+                &field("href"),
+                "Link mark must have `href`",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Merges adjacent or overlapping marks of the same kind and attributes.
+/// Merged mark keeps the first id of marks it was merged from,
+/// so marks of existing blocks are updated instead of recreated.
+/// Resulting marks are sorted by position
+pub fn normalize_marks(mut marks: Vec<ChangeMark>) -> Vec<ChangeMark> {
+    marks.sort_by_key(|m| (m.from, m.to));
+
+    let mut normalized: Vec<ChangeMark> = vec![];
+    for mark in marks {
+        // marks of one format never overlap in `normalized`,
+        // so the last one of the format is the only one `mark` can touch
+        let last_same_format = normalized
+            .iter_mut()
+            .rev()
+            .find(|n| n.kind == mark.kind && n.href == mark.href && n.color == mark.color);
+
+        match last_same_format {
+            Some(last) if mark.from <= last.to => {
+                last.to = max(last.to, mark.to);
+                if last.id.is_none() {
+                    last.id = mark.id;
+                }
+            }
+            _ => normalized.push(mark),
+        }
+    }
+
+    normalized
+}
+
+/// Part of text replaced by an edit, offsets are in `MARK_OFFSET_UNIT`:
+/// `[from, old_to)` of old text became `[from, new_to)` of new text
+#[derive(Debug, PartialEq, Eq)]
+struct TextEdit {
+    from: i32,
+    old_to: i32,
+    new_to: i32,
+}
+
+/// Finds the edited part of text as everything between common prefix and common suffix.
+/// `None` if text was not changed
+fn diff_text(old_text: &str, new_text: &str) -> Option<TextEdit> {
+    if old_text == new_text {
+        return None;
+    }
+
+    // compare characters not code units, so edit never splits a character
+    let old_chars: Vec<char> = old_text.chars().collect();
+    let new_chars: Vec<char> = new_text.chars().collect();
+
+    let prefix = old_chars
+        .iter()
+        .zip(new_chars.iter())
+        .take_while(|(o, n)| o == n)
+        .count();
+    let suffix = old_chars[prefix..]
+        .iter()
+        .rev()
+        .zip(new_chars[prefix..].iter().rev())
+        .take_while(|(o, n)| o == n)
+        .count();
+
+    Some(TextEdit {
+        from: units(&old_chars[..prefix]),
+        old_to: units(&old_chars[..old_chars.len() - suffix]),
+        new_to: units(&new_chars[..new_chars.len() - suffix]),
+    })
+}
+
+/// Moves marks along with the text they were set on when block text
+/// changes from `old_text` to `new_text`. Marks after the edit are shifted,
+/// marks overlapping it are shrunk or stretched over the replacement text
+/// and marks whose whole text was deleted are dropped
+pub fn shift_marks(old_text: &str, new_text: &str, marks: Vec<ChangeMark>) -> Vec<ChangeMark> {
+    let edit = match diff_text(old_text, new_text) {
+        Some(edit) => edit,
+        None => return marks,
+    };
+    let delta = edit.new_to - edit.old_to;
+
+    marks
+        .into_iter()
+        .filter_map(|mut mark| {
+            // text typed right at the mark start or end is not included in the mark
+            if mark.to <= edit.from {
+                return Some(mark);
+            }
+
+            mark.from = match mark.from {
+                f if f >= edit.old_to => f + delta,
+                f if f <= edit.from => f,
+                _ => edit.from,
+            };
+            mark.to = match mark.to {
+                t if t >= edit.old_to => t + delta,
+                _ => edit.new_to,
+            };
+
+            if mark.from < mark.to {
+                Some(mark)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Finds case insensitive occurrences of query words in text
+/// and returns their `(from, to)` ranges in `MARK_OFFSET_UNIT`, sorted and merged.
+/// Only whole words match, as in mongo text search, so `cat` is not found in `concatenate`.
+/// Negated words (`-word`) are skipped, as they never match
+pub fn find_words(text: &str, query: &str) -> Vec<(i32, i32)> {
+    let lower = |c: &char| c.to_lowercase().collect::<String>();
+    let text_chars: Vec<char> = text.chars().collect();
+    let text_lower: Vec<String> = text_chars.iter().map(lower).collect();
+    // query words are trimmed to alphanumerics, so boundary is a non alphanumeric neighbour
+    let is_word_boundary = |i: usize| {
+        i == 0
+            || i == text_chars.len()
+            || !text_chars[i - 1].is_alphanumeric()
+            || !text_chars[i].is_alphanumeric()
+    };
+
+    let mut found: Vec<(usize, usize)> = vec![];
+    for word in query.split_whitespace().filter(|w| !w.starts_with('-')) {
+        let word_lower: Vec<String> = word
+            .trim_matches(|c: char| !c.is_alphanumeric())
+            .chars()
+            .map(|c| lower(&c))
+            .collect();
+        if word_lower.is_empty() || word_lower.len() > text_lower.len() {
+            continue;
+        }
+
+        for start in 0..=text_lower.len() - word_lower.len() {
+            let end = start + word_lower.len();
+            if text_lower[start..end] == word_lower[..]
+                && is_word_boundary(start)
+                && is_word_boundary(end)
+            {
+                found.push((start, end));
+            }
+        }
+    }
+    found.sort();
+
+    let mut merged: Vec<(usize, usize)> = vec![];
+    for (from, to) in found {
+        match merged.last_mut() {
+            Some(last) if from <= last.1 => last.1 = max(last.1, to),
+            _ => merged.push((from, to)),
+        }
+    }
+
+    merged
+        .into_iter()
+        .map(|(from, to)| (units(&text_chars[..from]), units(&text_chars[..to])))
+        .collect()
+}
@@ -5,6 +5,8 @@ use async_graphql::SimpleObject;
 
 pub mod auth;
 pub mod groups;
+pub mod marks;
+pub mod ordering;
 pub mod stack;
 
 #[derive(InputObject)]
@@ -30,6 +32,9 @@ impl From<Option<HandlerPaging>> for Paging {
         if offset < 0 {
             offset = 0
         }
+        if limit < 0 {
+            limit = 0
+        }
 
         return Paging { offset, limit };
     }
@@ -0,0 +1,86 @@
+/// Digits of order keys, in ascending byte order so keys compare as plain strings
+const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const BASE: u32 = DIGITS.len() as u32;
+
+/// Keys longer than this are not generated, the set is rebalanced instead
+pub const MAX_ORDER_KEY_LEN: usize = 12;
+
+fn digit(key: &[u8], i: usize) -> Option<u32> {
+    key.get(i)
+        .map(|d| DIGITS.iter().position(|x| x == d).unwrap_or(0) as u32)
+}
+
+///
+/// Order key between two neighbour keys, `None` means start or end of the set.
+/// Keys are fractions `0.k1k2...` written in base 62 without trailing zeros,
+/// so their string order matches their numeric order and there is always
+/// a key between two others. Inserting at the set start or end picks the key
+/// closest to the neighbour, so keys grow slowly when groups are appended or prepended.
+/// Inserting between two keys takes the midpoint, so repeated inserts at the same
+/// place add a digit only once the gap is halved down to nothing
+///
+pub fn key_between(before: Option<&str>, after: Option<&str>) -> String {
+    let lo = before.unwrap_or("").as_bytes();
+    let hi = after.map(|a| a.as_bytes());
+
+    let mut key = vec![];
+    // while key is a prefix of `after` the next digit is bounded by it
+    let mut upper_bounded = hi.is_some();
+    let mut i = 0;
+    loop {
+        let l = digit(lo, i).unwrap_or(0);
+        let h = match hi.and_then(|hi| digit(hi, i)) {
+            Some(h) if upper_bounded => h,
+            _ => {
+                upper_bounded = false;
+                BASE
+            }
+        };
+
+        if h > l + 1 {
+            let d = if before.is_some() && after.is_none() {
+                l + 1
+            } else if before.is_none() && upper_bounded {
+                h - 1
+            } else {
+                (l + h) / 2
+            };
+            key.push(DIGITS[d as usize]);
+            break;
+        }
+
+        key.push(DIGITS[l as usize]);
+        if h > l {
+            upper_bounded = false;
+        }
+        i += 1;
+    }
+
+    String::from_utf8(key).unwrap()
+}
+
+/// `n` ascending keys spread evenly, used to rebalance a set which keys grew too long
+pub fn spread_keys(n: usize) -> Vec<String> {
+    let n = n as u128;
+    let mut width = 1;
+    while (BASE as u128).pow(width) <= n + 1 {
+        width += 1;
+    }
+    let scale = (BASE as u128).pow(width);
+
+    (1..=n)
+        .map(|i| {
+            let mut value = i * scale / (n + 1);
+            let mut key = vec![];
+            for _ in 0..width {
+                key.push(DIGITS[(value % BASE as u128) as usize]);
+                value /= BASE as u128;
+            }
+            key.reverse();
+            while key.last() == Some(&DIGITS[0]) {
+                key.pop();
+            }
+            String::from_utf8(key).unwrap()
+        })
+        .collect()
+}
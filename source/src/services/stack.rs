@@ -1,29 +1,45 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use shaku::{Component, Interface};
 use slog::Logger;
 
 use proc_macro::HasLogger;
 
+use crate::channels::stack::{StackChannelIf, StackItemEvent};
 use crate::errors::AppError;
-use crate::handlers::stack::{NewStackItem, StackItemChangeSet};
+use crate::handlers::stack::{
+    ChangeMark, InsertChangeSet, NewStackItem, StackItemChangeSet, UpdateBlock,
+};
 use crate::logger::AppLoggerIf;
 use crate::repos::blocks::BlocksRepoIf;
-use crate::repos::blocks::InsertBlock;
-use crate::repos::groups::GroupsRepoIf;
-use crate::repos::groups_ordering::GroupsOrderingRepoIf;
+use crate::repos::blocks::{Block as BlockEntity, InsertBlock};
+use crate::repos::group_items::{GroupItemsRepoIf, InsertGroupItem};
+use crate::repos::groups::{Group, GroupsRepoIf};
 use crate::repos::Id;
-use crate::repos::marks::{InsertMark, MarksRepoIf};
-use crate::repos::stack::{NewStackItem as NewStackItemEntity, StackRepoIf};
-use crate::repos::stack_history::{InsertHistoryBlock, InsertHistoryMark, StackHistoryRepoIf};
+use crate::repos::marks::{InsertMark, Mark as MarkEntity, MarkKind, MarksRepoIf};
+use crate::repos::stack::{
+    NewStackItem as NewStackItemEntity, StackItem as StackItemEntity, StackRepoIf,
+};
+use crate::repos::stack_history::{HistoryBlock, InsertHistoryBlock, StackHistoryRepoIf};
 use crate::repos::users::User;
+use crate::services::groups::PAGING_MAX_LIMIT;
+use crate::services::marks::{find_words, normalize_marks, shift_marks, validate_marks};
+use crate::services::ordering::{key_between, spread_keys, MAX_ORDER_KEY_LEN};
+use crate::services::{PageInfo, Paged, Paging};
 use crate::utils::{AppResult, Refs};
 
 #[derive(Debug, Clone)]
 pub struct StackItem {
     pub id: Id,
     pub blocks: Vec<Block>,
+    /// incremented on every change set applied to the item
+    pub version: i32,
+    /// groups the item is filed into
+    pub groups_ids: Vec<Id>,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -99,6 +115,68 @@ pub struct Mark {
     pub id: Id,
     pub from: i32,
     pub to: i32,
+    pub kind: MarkKind,
+    pub href: Option<String>,
+    pub color: Option<String>,
+}
+
+impl From<MarkEntity> for Mark {
+    fn from(mark: MarkEntity) -> Self {
+        Mark {
+            id: mark.id,
+            from: mark.from,
+            to: mark.to,
+            kind: mark.kind,
+            href: mark.href,
+            color: mark.color,
+        }
+    }
+}
+
+/// Block state saved in history before block was changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockVersion {
+    pub block_id: Id,
+    pub version: i32,
+    pub text: String,
+    pub marks: Vec<Mark>,
+}
+
+impl From<HistoryBlock> for BlockVersion {
+    fn from(history_block: HistoryBlock) -> Self {
+        BlockVersion {
+            block_id: history_block.block_id,
+            version: history_block.version,
+            text: history_block.text,
+            marks: history_block
+                .marks
+                .into_iter()
+                .map(|m| Mark {
+                    id: m.mark_id,
+                    from: m.from,
+                    to: m.to,
+                    kind: m.kind,
+                    href: m.href,
+                    color: m.color,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Stack item found by search
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub stack_item: StackItem,
+    pub highlights: Vec<Highlight>,
+}
+
+/// Part of block text matching search query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Highlight {
+    pub block_id: Id,
+    pub from: i32,
+    pub to: i32,
 }
 
 // impl PartialEq<InsertMark> for Mark {
@@ -113,6 +191,14 @@ pub struct Mark {
 //     }
 // }
 
+/// Items added before `created_at` was stored get it from their id
+fn created_at(item: &StackItemEntity) -> DateTime<Utc> {
+    match &item.created_at {
+        Some(created_at) => created_at.0,
+        None => item.id.oid().timestamp(),
+    }
+}
+
 #[async_trait]
 pub trait StackServiceIf: Interface {
     async fn add_to_my_stack(&self, user: User, stack_item: NewStackItem) -> AppResult<StackItem>;
@@ -121,7 +207,68 @@ pub trait StackServiceIf: Interface {
         user: User,
         changes: StackItemChangeSet,
     ) -> AppResult<StackItem>;
+    /// user stack items, last pushed first
     async fn my_stack(&self, user: User) -> Vec<StackItem>;
+    /// last pushed item which was not popped yet
+    async fn stack_peek(&self, user: User) -> Option<StackItem>;
+    /// Files the top item into group start and takes it off the stack.
+    /// Item is marked popped only after it is in the group, if popping fails
+    /// the item stays on top and popping it again just moves it to group start
+    async fn stack_pop(&self, user: User, into_group: Id) -> AppResult<StackItem>;
+    /// puts item back on top of the stack
+    async fn stack_push(
+        &self,
+        user: User,
+        stack_id: Id,
+        now: DateTime<Utc>,
+    ) -> AppResult<StackItem>;
+    /// full text search over texts of user stack items blocks
+    async fn search_my_stack(
+        &self,
+        user: User,
+        query: String,
+        paging: Paging,
+    ) -> AppResult<Paged<SearchHit>>;
+    /// moves stack item to trash
+    async fn remove_from_my_stack(
+        &self,
+        user: User,
+        stack_id: Id,
+        now: DateTime<Utc>,
+    ) -> AppResult<StackItem>;
+    async fn trash(&self, user: User) -> Vec<StackItem>;
+    async fn restore_from_trash(&self, user: User, stack_id: Id) -> AppResult<StackItem>;
+    /// deletes stack item from trash with all its blocks, marks and history
+    async fn purge_from_trash(&self, user: User, stack_id: Id) -> AppResult<Id>;
+    async fn block_history(&self, user: User, block_id: Id) -> AppResult<Vec<BlockVersion>>;
+    async fn restore_block_version(
+        &self,
+        user: User,
+        block_id: Id,
+        version: i32,
+    ) -> AppResult<StackItem>;
+    /// files stack item into group right after `after_stack_id`, to the group start if `None`.
+    /// Item already filed into the group is moved
+    async fn add_to_group(
+        &self,
+        user: User,
+        stack_id: Id,
+        group_id: Id,
+        after_stack_id: Option<Id>,
+    ) -> AppResult<StackItem>;
+    async fn remove_from_group(
+        &self,
+        user: User,
+        stack_id: Id,
+        group_id: Id,
+    ) -> AppResult<StackItem>;
+    /// group stack items in group order, items in trash are skipped
+    async fn group_items(
+        &self,
+        user: User,
+        group_id: Id,
+        paging: Paging,
+    ) -> AppResult<Paged<StackItem>>;
 }
 
 #[shaku(interface = StackServiceIf)]
@@ -131,7 +278,7 @@ pub struct StackService {
     groups_repo: Arc<dyn GroupsRepoIf>,
 
     #[shaku(inject)]
-    groups_ordering_repo: Arc<dyn GroupsOrderingRepoIf>,
+    group_items_repo: Arc<dyn GroupItemsRepoIf>,
 
     #[shaku(inject)]
     stack_repo: Arc<dyn StackRepoIf>,
@@ -145,6 +292,9 @@ pub struct StackService {
     #[shaku(inject)]
     stack_history_repo: Arc<dyn StackHistoryRepoIf>,
 
+    #[shaku(inject)]
+    stack_channel: Arc<dyn StackChannelIf>,
+
     #[logger]
     #[shaku(inject)]
     app_logger: Arc<dyn AppLoggerIf>,
@@ -161,41 +311,290 @@ impl StackService {
             .find_by_user_id_and_stack_item_id(user_id.clone(), stack_item_id.clone())
             .await?;
 
-        let blocks_ids: Vec<Id> = stack_item_entity.blocks_ids;
-        let marks_ids: Vec<Id> = stack_item_entity.marks_ids;
+        self.assemble_stack_items(vec![stack_item_entity])
+            .await
+            .pop()
+    }
+
+    /// Loads blocks and marks of stack item entities
+    async fn assemble_stack_items(
+        &self,
+        stack_item_entities: Vec<StackItemEntity>,
+    ) -> Vec<StackItem> {
+        let blocks_ids: Vec<Id> = stack_item_entities
+            .iter()
+            .map(|s| s.blocks_ids.clone())
+            .flatten()
+            .collect();
+        let marks_ids: Vec<Id> = stack_item_entities
+            .iter()
+            .map(|s| s.marks_ids.clone())
+            .flatten()
+            .collect();
+
+        let stacks_ids: Vec<Id> = stack_item_entities.iter().map(|s| s.id.clone()).collect();
 
         let blocks = self.blocks_repo.find_by_ids(blocks_ids.refs()).await;
         let marks = self.marks_repo.find_by_ids(marks_ids.refs()).await;
+        let group_items = self.group_items_repo.find_by_stacks_ids(&stacks_ids).await;
+
+        let mut stack = vec![];
+        for item in stack_item_entities {
+            let groups_ids = group_items
+                .iter()
+                .filter(|g| g.stack_id == item.id)
+                .map(|g| g.group_id.clone())
+                .collect();
+            let created_at = created_at(&item);
 
-        let mut stack_item_blocks = vec![];
-        for block_id in blocks_ids {
-            let block_entity = blocks.iter().find(|b| b.id == block_id).unwrap().clone();
-
-            let mut block_item_marks = vec![];
-            for mark_id in block_entity.marks_ids {
-                let mark_entity = marks.iter().find(|m| m.id == mark_id).unwrap().clone();
-                block_item_marks.push(Mark {
-                    id: mark_entity.id,
-                    from: mark_entity.from,
-                    to: mark_entity.to,
+            let mut stack_item_blocks = vec![];
+            for block_id in item.blocks_ids {
+                let block_entity = blocks.iter().find(|b| b.id == block_id).unwrap().clone();
+
+                let mut block_item_marks = vec![];
+                for mark_id in block_entity.marks_ids {
+                    let mark_entity = marks.iter().find(|m| m.id == mark_id).unwrap().clone();
+                    block_item_marks.push(mark_entity.into())
+                }
+
+                stack_item_blocks.push(Block {
+                    id: block_entity.id,
+                    stack_id: block_entity.stack_id,
+                    order: block_entity.order,
+                    text: block_entity.text,
+                    marks: block_item_marks,
+                    current_version: block_entity.current_version,
+                    initial_version: block_entity.initial_version,
                 })
             }
+            stack.push(StackItem {
+                id: item.id,
+                blocks: stack_item_blocks,
+                version: item.version,
+                groups_ids,
+                created_at,
+            })
+        }
+
+        stack
+    }
+
+    /// Inserts item into group or moves it there if it is already in the group
+    async fn file_into_group(
+        &self,
+        user: &User,
+        group: &Group,
+        stack_id: &Id,
+        after_stack_id: &Option<Id>,
+    ) {
+        let order_key = self.new_order_key_in_group(&group.id, after_stack_id).await;
+
+        match self
+            .group_items_repo
+            .find_by_group_id_and_stack_id(&group.id, stack_id)
+            .await
+        {
+            Some(group_item) => {
+                self.group_items_repo
+                    .set_order_key(&group_item.id, &order_key)
+                    .await;
+            }
+            None => {
+                self.group_items_repo
+                    .insert(&InsertGroupItem {
+                        user_id: user.id.clone(),
+                        group_id: group.id.clone(),
+                        stack_id: stack_id.clone(),
+                        order_key,
+                    })
+                    .await;
+            }
+        }
+    }
+
+    /// Group of the user which is not removed
+    async fn find_user_group(&self, user: &User, group_id: &Id) -> AppResult<Group> {
+        self.groups_repo
+            .find(group_id)
+            .await
+            .filter(|g| g.creator_id == user.id && !g.removed)
+            .ok_or(AppError::not_found("Group not found"))
+    }
+
+    ///
+    /// Ключ для элемента, вставляемого в группу после `after_stack_id` (в начало если `None`
+    /// или если `after_stack_id` нет в группе). Другие элементы группы не трогаются,
+    /// кроме случая когда ключи стали слишком длинными и группа перебалансируется
+    ///
+    async fn new_order_key_in_group(&self, group_id: &Id, after_stack_id: &Option<Id>) -> String {
+        let order_key = self.key_after_item_in_group(group_id, after_stack_id).await;
+        if order_key.len() <= MAX_ORDER_KEY_LEN {
+            return order_key;
+        }
+
+        let group_items = self.group_items_repo.get_by_group_id(group_id).await;
+        for (item, key) in group_items.iter().zip(spread_keys(group_items.len())) {
+            self.group_items_repo.set_order_key(&item.id, &key).await;
+        }
+
+        self.key_after_item_in_group(group_id, after_stack_id).await
+    }
+
+    async fn key_after_item_in_group(&self, group_id: &Id, after_stack_id: &Option<Id>) -> String {
+        let prev_key = match after_stack_id {
+            Some(stack_id) => self
+                .group_items_repo
+                .find_by_group_id_and_stack_id(group_id, stack_id)
+                .await
+                .map(|x| x.order_key),
+            None => None,
+        };
+        let next_key = self
+            .group_items_repo
+            .find_first_after(group_id, prev_key.as_deref())
+            .await
+            .map(|x| x.order_key);
+
+        key_between(prev_key.as_deref(), next_key.as_deref())
+    }
+
+    /// Moves block marks passed with ids, inserts marks passed without ids
+    /// and deletes block marks that were not passed at all.
+    /// Returns block marks ids in the same order marks were passed
+    async fn reconcile_marks(&self, block: &Block, change_marks: &Vec<ChangeMark>) -> Vec<Id> {
+        let removed_marks_ids: Vec<&Id> = block
+            .marks
+            .iter()
+            .filter(|m| !change_marks.iter().any(|c| c.id.as_ref() == Some(&m.id)))
+            .map(|m| &m.id)
+            .collect();
+        self.marks_repo.delete_many(removed_marks_ids).await;
+
+        for change_mark in change_marks {
+            if let Some(id) = &change_mark.id {
+                // presence of mark in block checked by `validate_change_set`
+                let old_mark = block.marks.iter().find(|m| &m.id == id).unwrap();
+                let mark_entity = MarkEntity {
+                    id: id.clone(),
+                    block_id: block.id.clone(),
+                    from: change_mark.from,
+                    to: change_mark.to,
+                    kind: change_mark.kind,
+                    href: change_mark.href.clone(),
+                    color: change_mark.color.clone(),
+                };
+                if Mark::from(mark_entity.clone()) != *old_mark {
+                    self.marks_repo.update(&mark_entity).await;
+                }
+            }
+        }
 
-            stack_item_blocks.push(Block {
-                id: block_entity.id,
-                stack_id: block_entity.stack_id,
-                order: block_entity.order,
-                text: block_entity.text,
-                marks: block_item_marks,
-                current_version: block_entity.current_version,
-                initial_version: block_entity.initial_version,
+        let new_marks: Vec<InsertMark> = change_marks
+            .iter()
+            .filter(|c| c.id.is_none())
+            .map(|c| InsertMark {
+                block_id: block.id.clone(),
+                from: c.from,
+                to: c.to,
+                kind: c.kind,
+                href: c.href.clone(),
+                color: c.color.clone(),
             })
+            .collect();
+        let mut inserted_marks_ids = self
+            .marks_repo
+            .insert_many(new_marks.refs())
+            .await
+            .into_iter()
+            .map(|m| m.id);
+
+        change_marks
+            .iter()
+            .map(|c| match &c.id {
+                Some(id) => id.clone(),
+                None => inserted_marks_ids.next().unwrap(),
+            })
+            .collect()
+    }
+}
+
+fn validate_change_set(stack_item: &StackItem, changes: &StackItemChangeSet) -> AppResult<()> {
+    let find_block = |id: &Id| stack_item.blocks.iter().find(|b| &b.id == id);
+
+    for removed_id in &changes.removed {
+        if find_block(removed_id).is_none() {
+            return Err(AppError::validation(&format!(
+                "Block `{}` not found in stack item",
+                removed_id
+            )));
         }
-        Some(StackItem {
-            id: stack_item_entity.id,
-            blocks: stack_item_blocks,
-        })
     }
+
+    for (i, update) in changes.updated.iter().enumerate() {
+        let block = find_block(&update.id).ok_or(AppError::validation(&format!(
+            "Block `{}` not found in stack item",
+            update.id
+        )))?;
+        // marks not passed are shifted by the service, so they are valid
+        let update_marks = match &update.marks {
+            Some(marks) => marks,
+            None => continue,
+        };
+        validate_marks(&format!("updated.{}.marks", i), &update.text, update_marks)?;
+
+        let mut seen_marks_ids: Vec<&Id> = vec![];
+        for mark_id in update_marks.iter().filter_map(|m| m.id.as_ref()) {
+            if !block.marks.iter().any(|m| &m.id == mark_id) {
+                return Err(AppError::validation(&format!(
+                    "Mark `{}` not found in block `{}`",
+                    mark_id, block.id
+                )));
+            }
+            if seen_marks_ids.contains(&mark_id) {
+                return Err(AppError::validation(&format!(
+                    "Mark `{}` passed more than once",
+                    mark_id
+                )));
+            }
+            seen_marks_ids.push(mark_id);
+        }
+    }
+
+    let remaining_blocks: Vec<&Block> = stack_item
+        .blocks
+        .iter()
+        .filter(|b| !changes.removed.contains(&b.id))
+        .collect();
+
+    let mut inserted_amount = 0;
+    if let Some(inserted) = &changes.inserted {
+        inserted_amount = inserted.blocks.len();
+
+        for (i, block) in inserted.blocks.iter().enumerate() {
+            validate_marks(
+                &format!("inserted.blocks.{}.marks", i),
+                &block.text,
+                &block.marks,
+            )?;
+        }
+
+        if let Some(after_id) = &inserted.insert_after_id {
+            if !remaining_blocks.iter().any(|b| &b.id == after_id) {
+                return Err(AppError::validation(&format!(
+                    "Block `{}` to insert after not found in stack item",
+                    after_id
+                )));
+            }
+        }
+    }
+
+    if remaining_blocks.len() + inserted_amount == 0 {
+        return Err(AppError::validation(
+            "Can not remove all blocks of stack item",
+        ));
+    }
+
+    Ok(())
 }
 
 #[async_trait]
@@ -209,6 +608,13 @@ impl StackServiceIf for StackService {
             return Err(AppError::validation("Can not add empty stack item"));
         }
 
+        let mut new_blocks = vec![];
+        for (i, b) in new_stack_item.blocks.into_iter().enumerate() {
+            let marks: Vec<ChangeMark> = b.marks.into_iter().map(Into::into).collect();
+            validate_marks(&format!("blocks.{}.marks", i), &b.text, &marks)?;
+            new_blocks.push((b.text, normalize_marks(marks)));
+        }
+
         // TODO это убарть
         // let ids = vec![Id("123".to_string())];
         // let ids2 = vec![Id("456".to_string())];
@@ -219,13 +625,17 @@ impl StackServiceIf for StackService {
         // let c = futures::join!(a, b);
         // TODO вот досюда
 
+        let now = Utc::now();
         let stack_item_entity = self
             .stack_repo
             .insert(&NewStackItemEntity {
-                user_id: user.id,
+                user_id: user.id.clone(),
                 blocks_ids: vec![],
                 marks_ids: vec![],
                 version: 0,
+                created_at: now.into(),
+                pushed_at: now.into(),
+                popped: false,
             })
             .await;
 
@@ -234,13 +644,13 @@ impl StackServiceIf for StackService {
 
         let mut marks = vec![];
         let mut marks_ids = vec![];
-        for (i, b) in new_stack_item.blocks.into_iter().enumerate() {
+        for (i, (text, block_marks)) in new_blocks.into_iter().enumerate() {
             let inserted_block = self
                 .blocks_repo
                 .insert(InsertBlock {
                     stack_id: stack_item_entity.id.clone(),
                     order: i as i32,
-                    text: b.text,
+                    text,
                     marks_ids: vec![],
                     current_version: 0,
                     initial_version: 0,
@@ -249,13 +659,15 @@ impl StackServiceIf for StackService {
 
             blocks_ids.push(inserted_block.id.clone());
 
-            let new_marks: Vec<InsertMark> = b
-                .marks
+            let new_marks: Vec<InsertMark> = block_marks
                 .iter()
                 .map(|x| InsertMark {
                     block_id: inserted_block.id.clone(),
                     from: x.from,
                     to: x.to,
+                    kind: x.kind,
+                    href: x.href.clone(),
+                    color: x.color.clone(),
                 })
                 .collect();
             let inserted_marks = self.marks_repo.insert_many(new_marks.refs()).await;
@@ -276,14 +688,7 @@ impl StackServiceIf for StackService {
                 stack_id: inserted_block.stack_id,
                 order: inserted_block.order,
                 text: inserted_block.text,
-                marks: inserted_marks
-                    .into_iter()
-                    .map(|m| Mark {
-                        id: m.id,
-                        from: m.from,
-                        to: m.to,
-                    })
-                    .collect(),
+                marks: inserted_marks.into_iter().map(Into::into).collect(),
                 current_version: 0,
                 initial_version: 0,
             })
@@ -299,10 +704,17 @@ impl StackServiceIf for StackService {
             .link_marks(&stack_item_entity, &marks_ids)
             .await;
 
-        Ok(StackItem {
+        let stack_item = StackItem {
+            created_at: created_at(&stack_item_entity),
             id: stack_item_entity.id,
             blocks,
-        })
+            version: stack_item_entity.version,
+            groups_ids: vec![],
+        };
+        self.stack_channel
+            .publish(&user.id, StackItemEvent::Created(stack_item.clone()));
+
+        Ok(stack_item)
     }
 
     async fn update_stack_item(
@@ -330,6 +742,28 @@ impl StackServiceIf for StackService {
             .await
             .ok_or(AppError::not_found("Stack item not found"))?;
 
+        validate_change_set(&old_stack_item, &changes)?;
+
+        // item version is claimed before any write,
+        // so of two concurrent change sets only one is applied
+        let stale = changes.version != old_stack_item.version
+            || !self
+                .stack_repo
+                .increment_version(&old_stack_item.id, changes.version)
+                .await;
+        if stale {
+            let current_version = self
+                .stack_repo
+                .find_by_user_id_and_stack_item_id(user.id.clone(), old_stack_item.id.clone())
+                .await
+                .map_or(old_stack_item.version, |s| s.version);
+
+            return Err(AppError::conflict(
+                "Stack item was changed by someone else",
+                current_version,
+            ));
+        }
+
         let removed_history_blocks: Vec<InsertHistoryBlock> = old_stack_item
             .blocks
             .iter()
@@ -339,15 +773,7 @@ impl StackServiceIf for StackService {
                 block_id: b.id.clone(),
                 version: b.current_version,
                 text: b.text.clone(),
-                marks: b
-                    .marks
-                    .iter()
-                    .map(|m| InsertHistoryMark {
-                        mark_id: m.id.clone(),
-                        from: m.from,
-                        to: m.to,
-                    })
-                    .collect(),
+                marks: b.marks.iter().map(|m| m.clone().into()).collect(),
             })
             .collect();
 
@@ -360,15 +786,7 @@ impl StackServiceIf for StackService {
                 block_id: b.id.clone(),
                 version: b.current_version,
                 text: b.text.clone(),
-                marks: b
-                    .marks
-                    .iter()
-                    .map(|m| InsertHistoryMark {
-                        mark_id: m.id.clone(),
-                        from: m.from,
-                        to: m.to,
-                    })
-                    .collect(),
+                marks: b.marks.iter().map(|m| m.clone().into()).collect(),
             })
             .collect();
 
@@ -379,278 +797,594 @@ impl StackServiceIf for StackService {
                 .insert_many(updated_history_blocks.refs()),
         );
 
-        // потом дропнуть удалённое
-        // инкрементить текущую версию
-        // потом обновить с новой версией
-        // потом добавить с инициальной = новой
+        // every change set produces one new version of the stack item,
+        // updated blocks move to it and inserted blocks start from it
+        let new_version = old_stack_item
+            .blocks
+            .iter()
+            .map(|b| b.current_version)
+            .max()
+            .unwrap_or(0)
+            + 1;
 
-        unimplemented!()
+        // drop removed blocks together with their marks
+        let removed_blocks: Vec<&Block> = old_stack_item
+            .blocks
+            .iter()
+            .filter(|b| changes.removed.contains(&b.id))
+            .collect();
+        let removed_marks_ids: Vec<&Id> = removed_blocks
+            .iter()
+            .map(|b| b.marks.iter().map(|m| &m.id))
+            .flatten()
+            .collect();
+        futures::join!(
+            self.blocks_repo
+                .delete_many(removed_blocks.iter().map(|b| &b.id).collect()),
+            self.marks_repo.delete_many(removed_marks_ids),
+        );
 
-        // rebuilt orders
-        //  orders [0, 2, 5] will be [0, 1, 2]
-        // changes.blocks.sort_by(|a, b| a.order.cmp(&b.order));
-        // for (i, block) in changes.blocks.iter_mut().enumerate() {
-        //     block.order = i as i32;
-        // }
-        //
-        // // First create history copy of blocks
+        let mut remaining_blocks: Vec<&Block> = old_stack_item
+            .blocks
+            .iter()
+            .filter(|b| !changes.removed.contains(&b.id))
+            .collect();
+        remaining_blocks.sort_by_key(|b| b.order);
 
-        //
-        // let deleted_blocks =
-        //     diff::get_deleted_blocks(&old_stack_item.blocks, &changes.blocks);
-        //
-        // let deleted_history_blocks: Vec<InsertHistoryBlock> = deleted_blocks
-        //     .iter()
-        //     .map(|x| x.clone().clone().into())
-        //     .collect();
-        //
-        // let updated_blocks =
-        //     diff::get_updated_blocks(&old_stack_item.blocks, &changes.blocks);
-        //
-        // let updated_history_blocks: Vec<InsertHistoryBlock> = updated_blocks
-        //     .iter()
-        //     .map(|(x, _)| x.clone().clone().into())
-        //     .collect();
-        //
-        // futures::join!(
-        //     self.stack_history_repo
-        //         .insert_many(deleted_history_blocks.refs()),
-        //     self.stack_history_repo
-        //         .insert_many(updated_history_blocks.refs())
-        // );
-        //
-        // // self.stack_history_repo
-        // //     .insert_many(deleted_history_blocks.refs())
-        // //     .await;
-        // //
-        // // self.stack_history_repo
-        // //     .insert_many(updated_history_blocks.refs())
-        // //     .await;
-        //
-        // let added_blocks = diff::get_new_blocks(&old_stack_item.blocks, &changes.blocks);
-        //
-        // // let
-        // println!("{:#?}", deleted_blocks);
-        //
-        // unimplemented!();
-        //
-        // // return Ok(old_stack_item);
-        // // println!("{:#?}", old_stack_item);
-        // // unimplemented!();
-        //
-        // let stack_item = self
-        //     .stack_repo
-        //     .find_by_user_id_and_stack_item_id(user.id, changes.id)
-        //     .await
-        //     .ok_or_not_found()?;
-        //
-        // let updated_blocks = changes.blocks;
-        // let old_blocks = self.blocks_repo.find_by_ids(&stack_item.blocks_ids).await;
-        //
-        // // println!("UU");
-        // // println!("{:?}", updated_blocks);
-        // // println!("PP");
-        // // println!("{:?}", old_blocks);
-        //
-        // let added_new_blocks: Vec<&UpdateBlock> = updated_blocks
-        //     .iter()
-        //     .filter(|u| match &u.id {
-        //         None => true,
-        //         Some(id) => old_blocks.iter().any(|p| p.id != *id),
-        //     })
-        //     .collect();
-        //
-        // // let mut marks_appeared_in_added_blocks = HashMap::new();
-        // for added in added_new_blocks {
-        //     // let inserted = self.blocks_repo.insert(&stack_item.id, &added.text).await;
-        //     // self.stack_repo
-        //     //     .link_blocks(&stack_item, &vec![inserted.id.clone()])
-        //     //     .await;
-        //     // marks_appeared_in_added_blocks.insert(inserted.id, &added.marks);
-        // }
-        //
-        // // println!("MARKS_IN_NEW_BLOCKS");
-        // // println!("{:?}", marks_appeared_in_added_blocks);
-        //
-        // let removed_old_blocks: Vec<&BlockEntity> = old_blocks
-        //     .iter()
-        //     .filter(|prev| {
-        //         !updated_blocks.iter().any(|u| match &u.id {
-        //             None => false,
-        //             Some(id) => id == &prev.id,
-        //         })
-        //     })
-        //     .collect();
-        //
-        // // println!("REMBLK");
-        // // println!("{:?}", removed_old_blocks);
-        //
-        // // let mut marks_exists_in_removed_blocks = HashMap::new();
-        // // for removed in removed_old_blocks {
-        // // self.blocks_repo.delete(&removed.id).await;
-        // // marks_exists_in_removed_blocks.insert(removed.id.clone(), removed.clone().marks_ids);
-        // // }
-        //
-        // let updated_old_blocks: Vec<&BlockEntity> = old_blocks
-        //     .iter()
-        //     .filter(|prev| {
-        //         updated_blocks.iter().any(|u| match &u.id {
-        //             None => false,
-        //             Some(id) => id == &prev.id,
-        //         })
-        //     })
-        //     .collect();
-        //
-        // println!("KK");
-        // println!("{:?}", updated_blocks);
-        //
-        // let mut old_marks_removed_from_updated_blocks = HashMap::new();
-        // let mut marks_modified_in_updated_blocks = HashMap::new();
-        // let mut new_marks_added_into_updated_blocks = HashMap::new();
-        //
-        // for old in updated_old_blocks {
-        //     if let Some(new) = updated_blocks.iter().find(|u| match &u.id {
-        //         None => false,
-        //         Some(id) => id == &old.id,
-        //     }) {
-        //         let old_block_marks = self.marks_repo.find_by_block_id(&old.id).await;
-        //         let old_marks_removed_from_updated_block: Vec<Id> = old_block_marks
-        //             .clone()
-        //             .into_iter()
-        //             .filter(|m| {
-        //                 !new.marks.iter().any(|new_m| match &new_m.id {
-        //                     None => false,
-        //                     Some(id) => id == &m.id,
-        //                 })
-        //             })
-        //             .map(|m| m.id.clone())
-        //             .collect();
-        //         old_marks_removed_from_updated_blocks
-        //             .insert(&old.id, old_marks_removed_from_updated_block);
-        //
-        //         let old_marks_modified_in_updated_block: Vec<(MarkEntity, &InsertMark)> =
-        //             old_block_marks
-        //                 .clone()
-        //                 .into_iter()
-        //                 .filter(|m| {
-        //                     new.marks.iter().any(|new_m| match &new_m.id {
-        //                         None => false,
-        //                         Some(id) => {
-        //                             id == &m.id && (m.from != new_m.from || m.to != new_m.to)
-        //                         }
-        //                     })
-        //                 })
-        //                 .map(|old_mark| {
-        //                     let new_mark = new
-        //                         .marks
-        //                         .iter()
-        //                         .find(|new_m| match &new_m.id {
-        //                             None => false,
-        //                             Some(new_m_id) => new_m_id == &old_mark.id,
-        //                         })
-        //                         .unwrap();
-        //                     (old_mark, new_mark)
-        //                 })
-        //                 .collect();
-        //         marks_modified_in_updated_blocks
-        //             .insert(&old.id, old_marks_modified_in_updated_block);
-        //
-        //         let new_marks_added_into_updated_block: Vec<&InsertMark> =
-        //             new.marks.iter().filter(|m| m.id.is_none()).collect();
-        //         new_marks_added_into_updated_blocks
-        //             .insert(&old.id, new_marks_added_into_updated_block);
-        //     };
-        // }
-        //
-        // println!("OMREM");
-        // println!("{:#?}", old_marks_removed_from_updated_blocks);
-        // println!("MUPD");
-        // println!("{:#?}", marks_modified_in_updated_blocks);
-        // println!("NM");
-        // println!("{:#?}", new_marks_added_into_updated_blocks);
-        //
-        // // let mut updated_blocks_and_marks: Vec<(&BlockEntity, &Vec<UpdateMark>)> = vec![];
-        // // for updated in updated_blocks {
-        // //     // updated_blocks_and_marks.push((&updated, &updated.))
-        // // }
-        //
-        // // Сначала добавляем все новые блоки
-        // // Потом отмечаем удалённые
-        // // Потом записываем обновлённые блоки с сохранением предыдущей версии
-        // //
-        // // flag - fresh, version, deleted
-        // // При удалении блока - history - копия, текущий блок обновляется как fresh, deleted, version + 1
-        // // При добавлении блока - fresh, version = 0
-        // // При изменении блока - history - копия, текущий блок обновляется как fresh, version + 1
-        // //
-        // // Что с марками
-        // // Марки могут
-        // //  не измениться
-        // //  остаться в том же блоке но сменить расположение
-        // //  переместиться в другой блок
-        // //  быть удалёнными
-        // //
-        //
-        // println!("UPD");
-        // println!("{:?}", updated_blocks);
-        //
-        // // for prev_b in prev_blocks {
-        // //
-        // // }
-        //
-        // unimplemented!()
+        // position in remaining blocks where inserted blocks go to
+        let insert_position = match &changes.inserted {
+            Some(InsertChangeSet {
+                insert_after_id: Some(after_id),
+                ..
+            }) => {
+                remaining_blocks
+                    .iter()
+                    .position(|b| &b.id == after_id)
+                    .unwrap()
+                    + 1
+            }
+            _ => 0,
+        };
+        let inserted_amount = match &changes.inserted {
+            Some(inserted) => inserted.blocks.len(),
+            None => 0,
+        };
+
+        // (order, block id, block marks ids) of every block stack item consists of after changes
+        let mut result_blocks: Vec<(i32, Id, Vec<Id>)> = vec![];
+
+        // update remaining blocks, recounting orders on the way
+        for (i, old_block) in remaining_blocks.iter().enumerate() {
+            let order = if i < insert_position {
+                i
+            } else {
+                i + inserted_amount
+            } as i32;
+
+            let mut block_entity = BlockEntity {
+                id: old_block.id.clone(),
+                stack_id: old_block.stack_id.clone(),
+                order,
+                text: old_block.text.clone(),
+                marks_ids: old_block.marks.iter().map(|m| m.id.clone()).collect(),
+                current_version: old_block.current_version,
+                initial_version: old_block.initial_version,
+                removed: false,
+            };
+
+            match changes.updated.iter().find(|u| u.id == old_block.id) {
+                Some(update) => {
+                    block_entity.text = update.text.clone();
+                    block_entity.current_version = new_version;
+                    let marks = match &update.marks {
+                        Some(marks) => marks.clone(),
+                        None => shift_marks(
+                            &old_block.text,
+                            &update.text,
+                            old_block.marks.iter().cloned().map(Into::into).collect(),
+                        ),
+                    };
+                    block_entity.marks_ids = self
+                        .reconcile_marks(old_block, &normalize_marks(marks))
+                        .await;
+                    self.blocks_repo.update(&block_entity).await;
+                }
+                None => {
+                    if old_block.order != order {
+                        self.blocks_repo.update(&block_entity).await;
+                    }
+                }
+            }
+
+            result_blocks.push((order, block_entity.id, block_entity.marks_ids));
+        }
+
+        // insert new blocks with their marks
+        if let Some(inserted) = changes.inserted {
+            for (i, b) in inserted.blocks.into_iter().enumerate() {
+                let inserted_block = self
+                    .blocks_repo
+                    .insert(InsertBlock {
+                        stack_id: old_stack_item.id.clone(),
+                        order: (insert_position + i) as i32,
+                        text: b.text,
+                        marks_ids: vec![],
+                        current_version: new_version,
+                        initial_version: new_version,
+                    })
+                    .await;
+
+                let new_marks: Vec<InsertMark> = normalize_marks(b.marks)
+                    .iter()
+                    .map(|x| InsertMark {
+                        block_id: inserted_block.id.clone(),
+                        from: x.from,
+                        to: x.to,
+                        kind: x.kind,
+                        href: x.href.clone(),
+                        color: x.color.clone(),
+                    })
+                    .collect();
+                let inserted_marks_ids: Vec<Id> = self
+                    .marks_repo
+                    .insert_many(new_marks.refs())
+                    .await
+                    .into_iter()
+                    .map(|m| m.id)
+                    .collect();
+
+                self.blocks_repo
+                    .link_marks(&inserted_block, &inserted_marks_ids)
+                    .await;
+
+                result_blocks.push((inserted_block.order, inserted_block.id, inserted_marks_ids));
+            }
+        }
+
+        result_blocks.sort_by_key(|(order, _, _)| *order);
+        let mut blocks_ids: Vec<Id> = vec![];
+        let mut marks_ids: Vec<Id> = vec![];
+        for (_, block_id, block_marks_ids) in result_blocks {
+            blocks_ids.push(block_id);
+            marks_ids.extend(block_marks_ids);
+        }
+
+        self.stack_repo
+            .update(&StackItemEntity {
+                id: old_stack_item.id.clone(),
+                blocks_ids,
+                marks_ids,
+                version: changes.version + 1,
+                created_at: None,
+                pushed_at: None,
+                popped: false,
+            })
+            .await;
+
+        let stack_item = self
+            .find_stack_item_by_user_id_and_stack_item_id(&user.id, &old_stack_item.id)
+            .await
+            .ok_or(AppError::not_found("Stack item not found"))?;
+        self.stack_channel
+            .publish(&user.id, StackItemEvent::Updated(stack_item.clone()));
+
+        Ok(stack_item)
     }
 
     // TODO переписать чтобы выбирались блоки по stack_id с учётом moment = true
     async fn my_stack(&self, user: User) -> Vec<StackItem> {
         let stack_item_entities = self.stack_repo.find_by_user_id(user.id).await;
+        self.assemble_stack_items(stack_item_entities).await
+    }
 
-        let blocks_ids: Vec<Id> = stack_item_entities
-            .iter()
-            .map(|s| s.blocks_ids.clone())
-            .flatten()
+    async fn stack_peek(&self, user: User) -> Option<StackItem> {
+        let stack_item_entity = self.stack_repo.find_top_by_user_id(user.id).await?;
+        self.assemble_stack_items(vec![stack_item_entity])
+            .await
+            .pop()
+    }
+
+    async fn stack_pop(&self, user: User, into_group: Id) -> AppResult<StackItem> {
+        let group = self.find_user_group(&user, &into_group).await?;
+
+        let stack_item_entity = self
+            .stack_repo
+            .find_top_by_user_id(user.id.clone())
+            .await
+            .ok_or(AppError::not_found("Stack is empty"))?;
+
+        self.file_into_group(&user, &group, &stack_item_entity.id, &None)
+            .await;
+        if !self.stack_repo.pop(&stack_item_entity.id).await {
+            return Err(AppError::general("Stack item was popped by someone else"));
+        }
+
+        let stack_item = self
+            .assemble_stack_items(vec![stack_item_entity])
+            .await
+            .pop()
+            .ok_or(AppError::internal())?;
+        self.stack_channel
+            .publish(&user.id, StackItemEvent::Updated(stack_item.clone()));
+
+        Ok(stack_item)
+    }
+
+    async fn stack_push(
+        &self,
+        user: User,
+        stack_id: Id,
+        now: DateTime<Utc>,
+    ) -> AppResult<StackItem> {
+        let stack_item_entity = self
+            .stack_repo
+            .find_by_user_id_and_stack_item_id(user.id.clone(), stack_id)
+            .await
+            .ok_or(AppError::not_found("Stack item not found"))?;
+
+        // item could be moved to trash in between
+        if !self.stack_repo.push(&stack_item_entity.id, now).await {
+            return Err(AppError::not_found("Stack item not found"));
+        }
+
+        let stack_item = self
+            .find_stack_item_by_user_id_and_stack_item_id(&user.id, &stack_item_entity.id)
+            .await
+            .ok_or(AppError::not_found("Stack item not found"))?;
+        self.stack_channel
+            .publish(&user.id, StackItemEvent::Updated(stack_item.clone()));
+
+        Ok(stack_item)
+    }
+
+    async fn search_my_stack(
+        &self,
+        user: User,
+        query: String,
+        paging: Paging,
+    ) -> AppResult<Paged<SearchHit>> {
+        if query.trim().is_empty() {
+            return Err(AppError::field_validation(
+                "query",
+                "Search query can not be empty",
+            ));
+        }
+        let limit = paging.limit.min(PAGING_MAX_LIMIT);
+
+        let stacks_ids: Vec<Id> = self
+            .stack_repo
+            .find_by_user_id(user.id)
+            .await
+            .into_iter()
+            .map(|s| s.id)
             .collect();
-        let marks_ids: Vec<Id> = stack_item_entities
+        let found_ids = self
+            .blocks_repo
+            .search_stacks_ids(&stacks_ids, &query)
+            .await;
+
+        let page_ids: Vec<Id> = found_ids
             .iter()
-            .map(|s| s.marks_ids.clone())
-            .flatten()
+            .skip(paging.offset as usize)
+            .take(limit as usize)
+            .cloned()
             .collect();
+        let page_entities: Vec<StackItemEntity> =
+            self.stack_repo.find_by_ids(page_ids.refs()).await;
+        let mut page_items = self.assemble_stack_items(page_entities).await;
+        // keep search order, repo returns items in arbitrary order
+        page_items.sort_by_key(|i| page_ids.iter().position(|id| id == &i.id));
 
-        let blocks = self.blocks_repo.find_by_ids(blocks_ids.refs()).await;
-        let marks = self.marks_repo.find_by_ids(marks_ids.refs()).await;
-
-        let mut stack = vec![];
-        for item in stack_item_entities {
-            let mut stack_item_blocks = vec![];
-            for block_id in item.blocks_ids {
-                let block_entity = blocks.iter().find(|b| b.id == block_id).unwrap().clone();
-
-                let mut block_item_marks = vec![];
-                for mark_id in block_entity.marks_ids {
-                    let mark_entity = marks.iter().find(|m| m.id == mark_id).unwrap().clone();
-                    block_item_marks.push(Mark {
-                        id: mark_entity.id,
-                        from: mark_entity.from,
-                        to: mark_entity.to,
+        let objects = page_items
+            .into_iter()
+            .map(|stack_item| {
+                let highlights = stack_item
+                    .blocks
+                    .iter()
+                    .map(|b| {
+                        find_words(&b.text, &query)
+                            .into_iter()
+                            .map(move |(from, to)| Highlight {
+                                block_id: b.id.clone(),
+                                from,
+                                to,
+                            })
                     })
+                    .flatten()
+                    .collect();
+
+                SearchHit {
+                    stack_item,
+                    highlights,
                 }
+            })
+            .collect();
 
-                stack_item_blocks.push(Block {
-                    id: block_entity.id,
-                    stack_id: block_entity.stack_id,
-                    order: block_entity.order,
-                    text: block_entity.text,
-                    marks: block_item_marks,
-                    current_version: block_entity.current_version,
-                    initial_version: block_entity.initial_version,
-                })
+        Ok(Paged {
+            objects,
+            page_info: PageInfo {
+                offset: paging.offset,
+                limit,
+                total: Some(found_ids.len() as i32),
+            },
+        })
+    }
+
+    async fn remove_from_my_stack(
+        &self,
+        user: User,
+        stack_id: Id,
+        now: DateTime<Utc>,
+    ) -> AppResult<StackItem> {
+        let stack_item = self
+            .find_stack_item_by_user_id_and_stack_item_id(&user.id, &stack_id)
+            .await
+            .ok_or(AppError::not_found("Stack item not found"))?;
+
+        self.stack_repo.mark_removed(&stack_item.id, now).await;
+        futures::future::join_all(
+            stack_item
+                .blocks
+                .iter()
+                .map(|b| self.blocks_repo.mark_removed(&b.id)),
+        )
+        .await;
+
+        self.stack_channel
+            .publish(&user.id, StackItemEvent::Deleted(stack_item.id.clone()));
+
+        Ok(stack_item)
+    }
+
+    async fn trash(&self, user: User) -> Vec<StackItem> {
+        let stack_item_entities = self.stack_repo.find_removed_by_user_id(user.id).await;
+        self.assemble_stack_items(stack_item_entities).await
+    }
+
+    async fn restore_from_trash(&self, user: User, stack_id: Id) -> AppResult<StackItem> {
+        let stack_item_entity = self
+            .stack_repo
+            .find_removed_by_user_id_and_stack_item_id(user.id.clone(), stack_id)
+            .await
+            .ok_or(AppError::not_found("Stack item not found in trash"))?;
+
+        self.stack_repo.unmark_removed(&stack_item_entity.id).await;
+        futures::future::join_all(
+            stack_item_entity
+                .blocks_ids
+                .iter()
+                .map(|id| self.blocks_repo.unmark_removed(id)),
+        )
+        .await;
+
+        let stack_item = self
+            .find_stack_item_by_user_id_and_stack_item_id(&user.id, &stack_item_entity.id)
+            .await
+            .ok_or(AppError::not_found("Stack item not found"))?;
+        self.stack_channel
+            .publish(&user.id, StackItemEvent::Created(stack_item.clone()));
+
+        Ok(stack_item)
+    }
+
+    async fn purge_from_trash(&self, user: User, stack_id: Id) -> AppResult<Id> {
+        let stack_item_entity = self
+            .stack_repo
+            .find_removed_by_user_id_and_stack_item_id(user.id, stack_id)
+            .await
+            .ok_or(AppError::not_found("Stack item not found in trash"))?;
+
+        futures::join!(
+            self.marks_repo
+                .delete_many(stack_item_entity.marks_ids.refs()),
+            self.blocks_repo
+                .delete_many(stack_item_entity.blocks_ids.refs()),
+            self.stack_history_repo
+                .delete_by_stack_id(&stack_item_entity.id),
+            self.group_items_repo
+                .delete_by_stack_id(&stack_item_entity.id),
+        );
+        self.stack_repo.delete(&stack_item_entity.id).await;
+
+        Ok(stack_item_entity.id)
+    }
+
+    async fn block_history(&self, user: User, block_id: Id) -> AppResult<Vec<BlockVersion>> {
+        let versions = self.stack_history_repo.find_by_block_id(&block_id).await;
+
+        // block may be already removed from stack item, so its stack item taken from history
+        let stack_id = match versions.first() {
+            Some(v) => v.stack_id.clone(),
+            None => {
+                self.blocks_repo
+                    .find(&block_id)
+                    .await
+                    .ok_or(AppError::not_found("Block not found"))?
+                    .stack_id
             }
-            stack.push(StackItem {
-                id: item.id,
-                blocks: stack_item_blocks,
+        };
+
+        self.stack_repo
+            .find_by_user_id_and_stack_item_id(user.id, stack_id)
+            .await
+            .ok_or(AppError::not_found("Block not found"))?;
+
+        Ok(versions.into_iter().map(Into::into).collect())
+    }
+
+    async fn restore_block_version(
+        &self,
+        user: User,
+        block_id: Id,
+        version: i32,
+    ) -> AppResult<StackItem> {
+        let block_entity = self
+            .blocks_repo
+            .find(&block_id)
+            .await
+            .filter(|b| !b.removed)
+            .ok_or(AppError::not_found("Block not found"))?;
+
+        let stack_item = self
+            .find_stack_item_by_user_id_and_stack_item_id(&user.id, &block_entity.stack_id)
+            .await
+            .ok_or(AppError::not_found("Block not found"))?;
+
+        if block_entity.current_version == version {
+            return Err(AppError::validation(&format!(
+                "Block is already at version `{}`",
+                version
+            )));
+        }
+
+        let restored: BlockVersion = self
+            .stack_history_repo
+            .find_by_block_id_and_version(&block_id, version)
+            .await
+            .ok_or(AppError::not_found("Block version not found"))?
+            .into();
+
+        // marks still existing in block are moved back, the rest are created again
+        let marks = restored
+            .marks
+            .into_iter()
+            .map(|m| ChangeMark {
+                id: match block_entity.marks_ids.contains(&m.id) {
+                    true => Some(m.id),
+                    false => None,
+                },
+                from: m.from,
+                to: m.to,
+                kind: m.kind,
+                href: m.href,
+                color: m.color,
             })
+            .collect();
+
+        // restoring is an ordinary update, so current block state goes to history
+        self.update_stack_item(
+            user,
+            StackItemChangeSet {
+                stack_id: stack_item.id,
+                version: stack_item.version,
+                inserted: None,
+                removed: vec![],
+                updated: vec![UpdateBlock {
+                    id: block_id,
+                    text: restored.text,
+                    marks: Some(marks),
+                }],
+            },
+        )
+        .await
+    }
+
+    async fn add_to_group(
+        &self,
+        user: User,
+        stack_id: Id,
+        group_id: Id,
+        after_stack_id: Option<Id>,
+    ) -> AppResult<StackItem> {
+        self.find_stack_item_by_user_id_and_stack_item_id(&user.id, &stack_id)
+            .await
+            .ok_or(AppError::not_found("Stack item not found"))?;
+        let group = self.find_user_group(&user, &group_id).await?;
+
+        self.file_into_group(&user, &group, &stack_id, &after_stack_id)
+            .await;
+
+        let stack_item = self
+            .find_stack_item_by_user_id_and_stack_item_id(&user.id, &stack_id)
+            .await
+            .ok_or(AppError::not_found("Stack item not found"))?;
+        self.stack_channel
+            .publish(&user.id, StackItemEvent::Updated(stack_item.clone()));
+
+        Ok(stack_item)
+    }
+
+    async fn remove_from_group(
+        &self,
+        user: User,
+        stack_id: Id,
+        group_id: Id,
+    ) -> AppResult<StackItem> {
+        self.find_stack_item_by_user_id_and_stack_item_id(&user.id, &stack_id)
+            .await
+            .ok_or(AppError::not_found("Stack item not found"))?;
+        let group = self.find_user_group(&user, &group_id).await?;
+
+        let group_item = self
+            .group_items_repo
+            .find_by_group_id_and_stack_id(&group.id, &stack_id)
+            .await
+            .ok_or(AppError::not_found("Stack item not found in group"))?;
+        self.group_items_repo.delete(&group_item.id).await;
+
+        let stack_item = self
+            .find_stack_item_by_user_id_and_stack_item_id(&user.id, &stack_id)
+            .await
+            .ok_or(AppError::not_found("Stack item not found"))?;
+        self.stack_channel
+            .publish(&user.id, StackItemEvent::Updated(stack_item.clone()));
+
+        Ok(stack_item)
+    }
+
+    async fn group_items(
+        &self,
+        user: User,
+        group_id: Id,
+        paging: Paging,
+    ) -> AppResult<Paged<StackItem>> {
+        if paging.limit > PAGING_MAX_LIMIT {
+            return Err(AppError::validation(&format!(
+                "Paging limit can not be more then {}",
+                PAGING_MAX_LIMIT
+            )));
         }
+        let group = self.find_user_group(&user, &group_id).await?;
 
-        stack
+        let group_stacks_ids: Vec<Id> = self
+            .group_items_repo
+            .get_by_group_id(&group.id)
+            .await
+            .into_iter()
+            .map(|i| i.stack_id)
+            .collect();
+        // items in trash keep their place in group to get it back when restored
+        let mut listed: HashMap<Id, StackItemEntity> = self
+            .stack_repo
+            .find_by_user_id_and_ids(&user.id, group_stacks_ids.refs())
+            .await
+            .into_iter()
+            .map(|s| (s.id.clone(), s))
+            .collect();
+        let group_stacks_ids: Vec<Id> = group_stacks_ids
+            .into_iter()
+            .filter(|id| listed.contains_key(id))
+            .collect();
+
+        let page_ids: Vec<Id> = group_stacks_ids
+            .iter()
+            .skip(paging.offset as usize)
+            .take(paging.limit as usize)
+            .cloned()
+            .collect();
+        let page_entities: Vec<StackItemEntity> =
+            page_ids.iter().filter_map(|id| listed.remove(id)).collect();
+        let mut page_items = self.assemble_stack_items(page_entities).await;
+        // keep group order, repo returns items in arbitrary order
+        page_items.sort_by_key(|i| page_ids.iter().position(|id| id == &i.id));
+
+        Ok(Paged {
+            objects: page_items,
+            page_info: PageInfo {
+                offset: paging.offset,
+                limit: paging.limit,
+                total: Some(group_stacks_ids.len() as i32),
+            },
+        })
     }
 }
@@ -92,7 +92,17 @@ pub trait ExtendType<T> {
 
 impl<T> ExtendType<T> for AppResult<T> {
     fn extend_type(self) -> async_graphql::Result<T> {
-        self.map_err(|ee| ee.extend_with(|_, e| e.set("type", ee.get_type())))
+        self.map_err(|ee| {
+            ee.extend_with(|_, e| {
+                e.set("type", ee.get_type());
+                if let Some(field) = ee.get_field() {
+                    e.set("field", field);
+                }
+                if let Some(version) = ee.get_current_version() {
+                    e.set("currentVersion", version);
+                }
+            })
+        })
     }
 }
 
@@ -35,8 +35,10 @@ lazy_static! {
         mongo_pool_size: 100,
         db_name: "motor_test".to_string(),
         pwd_min_len: 6,
+        recent_sets_max: 10,
         access_token_lifetime: Duration::hours(1),
         refresh_token_lifetime: Duration::days(14),
+        token_secret: "test_secret".to_string(),
         clear_logger_files: true,
         loggers_json_pretty: true,
         app_logger_file: "../log/app_test.json".to_string(),
@@ -57,8 +59,10 @@ pub async fn setup() -> Container {
         mongo_pool_size: 100,
         db_name: "motor_test".to_string(),
         pwd_min_len: 6,
+        recent_sets_max: 10,
         access_token_lifetime: Duration::hours(1),
         refresh_token_lifetime: Duration::days(14),
+        token_secret: "test_secret".to_string(),
         clear_logger_files: true,
         loggers_json_pretty: true,
         app_logger_file: "../log/app_test.json".to_string(),
@@ -1,10 +1,15 @@
+use bson::Document;
 use chrono::{Duration, Utc};
+use futures::{FutureExt, StreamExt};
 use shaku::HasComponent;
 
+use motor_back::channels::sessions::{SessionEvent, SessionsChannelIf};
 use motor_back::container::Container;
 use motor_back::db::DBIf;
 use motor_back::errors::AppError;
 use motor_back::init::init_app;
+use motor_back::mongo::migrations::migrate;
+use motor_back::repos::tokens::{hash_token, SessionMeta};
 use motor_back::services::auth::AuthServiceIf;
 
 use crate::{DEFAULT_CONFIG, trunc_collection};
@@ -97,7 +102,7 @@ async fn can_not_login_with_incorrect_creds() -> () {
     let reg_result = auth.register("User2".to_string(), "12".to_string()).await;
     assert_eq!(reg_result, Ok(()));
 
-    let login_result = auth.login("User3".to_string(), "12".to_string(), Utc::now()).await;
+    let login_result = auth.login("User3".to_string(), "12".to_string(), SessionMeta::default(), Utc::now()).await;
     assert_eq!(login_result.is_err(), true);
 }
 
@@ -115,7 +120,7 @@ async fn can_login_with_ok_creds() -> () {
     let reg_result = auth.register("User3".to_string(), "123".to_string()).await;
     assert_eq!(reg_result, Ok(()));
 
-    let login_result = auth.login("User3".to_string(), "123".to_string(), Utc::now()).await;
+    let login_result = auth.login("User3".to_string(), "123".to_string(), SessionMeta::default(), Utc::now()).await;
     assert!(login_result.is_ok());
 }
 
@@ -150,7 +155,7 @@ async fn refresh_success_with_correct_token() -> () {
     let reg_result = auth.register("User101".to_string(), "321000".to_string()).await;
     assert_eq!(reg_result, Ok(()));
 
-    let tokens = auth.login("User101".to_string(), "321000".to_string(), Utc::now()).await.unwrap();
+    let tokens = auth.login("User101".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
 
     let refresh_result = auth.refresh_token(&tokens.refresh, Utc::now()).await;
     assert!(refresh_result.is_ok());
@@ -173,7 +178,7 @@ async fn refresh_failed_with_expired_token() -> () {
     let reg_result = auth.register("User1011".to_string(), "321000".to_string()).await;
     assert_eq!(reg_result, Ok(()));
 
-    let tokens = auth.login("User1011".to_string(), "321000".to_string(), Utc::now()).await.unwrap();
+    let tokens = auth.login("User1011".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
 
     let refresh_result = auth.refresh_token(&tokens.refresh, Utc::now()).await;
     assert_eq!(refresh_result.map(|_|()), Err(AppError::unauthorized()));
@@ -214,9 +219,346 @@ async fn validation_passed_for_correct_access() -> () {
     let reg_result = auth.register("User10112".to_string(), "321000".to_string()).await;
     assert_eq!(reg_result, Ok(()));
 
-    let tokens = auth.login("User10112".to_string(), "321000".to_string(), Utc::now()).await.unwrap();
+    let tokens = auth.login("User10112".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
 
     let result = auth.validate_access(&tokens.access, Utc::now()).await;
     assert!(result.is_ok());
     assert_eq!(result.unwrap().username, "User10112");
 }
+
+#[actix_rt::test]
+async fn validation_returns_when_access_expires() -> () {
+    let config = (&*DEFAULT_CONFIG).clone();
+    let ctr: Container = init_app(&config).await;
+
+    let db: &dyn DBIf = ctr.resolve_ref();
+    trunc_collection(&db.get(), "users").await;
+    trunc_collection(&db.get(), "tokens").await;
+
+    let auth: &dyn AuthServiceIf = ctr.resolve_ref();
+
+    let reg_result = auth.register("User10113".to_string(), "321000".to_string()).await;
+    assert_eq!(reg_result, Ok(()));
+
+    let tokens = auth.login("User10113".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
+
+    let (user, expires_at) = auth
+        .validate_access_with_expiry(&tokens.access, Utc::now())
+        .await
+        .unwrap();
+    assert_eq!(user.username, "User10113");
+    assert_eq!(expires_at, tokens.access_lifetime);
+}
+
+#[actix_rt::test]
+async fn logout_revokes_tokens_of_session() -> () {
+    let config = (&*DEFAULT_CONFIG).clone();
+    let ctr: Container = init_app(&config).await;
+
+    let db: &dyn DBIf = ctr.resolve_ref();
+    trunc_collection(&db.get(), "users").await;
+    trunc_collection(&db.get(), "tokens").await;
+
+    let auth: &dyn AuthServiceIf = ctr.resolve_ref();
+
+    auth.register("User20001".to_string(), "321000".to_string()).await.unwrap();
+    let tokens = auth.login("User20001".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
+    let other = auth.login("User20001".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
+
+    assert_eq!(auth.logout(&tokens.access).await, Ok(()));
+
+    let result = auth.validate_access(&tokens.access, Utc::now()).await;
+    assert_eq!(result.map(|_|()), Err(AppError::unauthorized()));
+    let result = auth.refresh_token(&tokens.refresh, Utc::now()).await;
+    assert_eq!(result.map(|_|()), Err(AppError::unauthorized()));
+    // other session still works
+    assert!(auth.validate_access(&other.access, Utc::now()).await.is_ok());
+}
+
+#[actix_rt::test]
+async fn sessions_listed_and_revoked_one_by_one() -> () {
+    let config = (&*DEFAULT_CONFIG).clone();
+    let ctr: Container = init_app(&config).await;
+
+    let db: &dyn DBIf = ctr.resolve_ref();
+    trunc_collection(&db.get(), "users").await;
+    trunc_collection(&db.get(), "tokens").await;
+
+    let auth: &dyn AuthServiceIf = ctr.resolve_ref();
+
+    auth.register("User20002".to_string(), "321000".to_string()).await.unwrap();
+    let now = Utc::now();
+    let laptop = SessionMeta {
+        user_agent: Some("Firefox".to_string()),
+        ip: Some("10.0.0.1".to_string()),
+    };
+    let phone = SessionMeta {
+        user_agent: Some("Safari".to_string()),
+        ip: Some("10.0.0.2".to_string()),
+    };
+    let laptop_tokens = auth.login("User20002".to_string(), "321000".to_string(), laptop, now).await.unwrap();
+    let phone_tokens = auth.login("User20002".to_string(), "321000".to_string(), phone, now + Duration::seconds(1)).await.unwrap();
+
+    let user = auth.validate_access(&laptop_tokens.access, Utc::now()).await.unwrap();
+    let sessions = auth.sessions(&user, &laptop_tokens.access, Utc::now()).await;
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0].user_agent, Some("Safari".to_string()));
+    assert_eq!(sessions[0].ip, Some("10.0.0.2".to_string()));
+    assert!(!sessions[0].current);
+    assert_eq!(sessions[1].user_agent, Some("Firefox".to_string()));
+    assert!(sessions[1].current);
+
+    assert_eq!(auth.revoke_session(&user, &sessions[0].id).await, Ok(()));
+    let result = auth.validate_access(&phone_tokens.access, Utc::now()).await;
+    assert_eq!(result.map(|_|()), Err(AppError::unauthorized()));
+    assert_eq!(auth.sessions(&user, &laptop_tokens.access, Utc::now()).await.len(), 1);
+
+    let result = auth.revoke_session(&user, &sessions[0].id).await;
+    assert_eq!(result, Err(AppError::not_found("Session not found")));
+}
+
+#[actix_rt::test]
+async fn logout_everywhere_revokes_all_sessions() -> () {
+    let config = (&*DEFAULT_CONFIG).clone();
+    let ctr: Container = init_app(&config).await;
+
+    let db: &dyn DBIf = ctr.resolve_ref();
+    trunc_collection(&db.get(), "users").await;
+    trunc_collection(&db.get(), "tokens").await;
+
+    let auth: &dyn AuthServiceIf = ctr.resolve_ref();
+
+    auth.register("User20003".to_string(), "321000".to_string()).await.unwrap();
+    let first = auth.login("User20003".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
+    let second = auth.login("User20003".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
+
+    let user = auth.validate_access(&first.access, Utc::now()).await.unwrap();
+    assert_eq!(auth.logout_everywhere(&user).await, Ok(()));
+
+    assert!(auth.validate_access(&first.access, Utc::now()).await.is_err());
+    assert!(auth.validate_access(&second.access, Utc::now()).await.is_err());
+    assert!(auth.sessions(&user, &first.access, Utc::now()).await.is_empty());
+}
+
+#[actix_rt::test]
+async fn revoked_sessions_published_to_user_subscribers() -> () {
+    let config = (&*DEFAULT_CONFIG).clone();
+    let ctr: Container = init_app(&config).await;
+
+    let db: &dyn DBIf = ctr.resolve_ref();
+    trunc_collection(&db.get(), "users").await;
+    trunc_collection(&db.get(), "tokens").await;
+
+    let auth: &dyn AuthServiceIf = ctr.resolve_ref();
+    let channel: &dyn SessionsChannelIf = ctr.resolve_ref();
+
+    auth.register("User20008".to_string(), "321000".to_string()).await.unwrap();
+    let first = auth.login("User20008".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
+    let second = auth.login("User20008".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
+    let third = auth.login("User20008".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
+    let user = auth.validate_access(&first.access, Utc::now()).await.unwrap();
+
+    let mut revoked = channel.subscribe(&user.id);
+    assert!(revoked.next().now_or_never().is_none());
+
+    auth.logout(&first.access).await.unwrap();
+    assert!(matches!(revoked.next().await, Some(SessionEvent::Revoked)));
+
+    let session = auth.sessions(&user, &third.access, Utc::now()).await
+        .into_iter()
+        .find(|s| !s.current)
+        .unwrap();
+    auth.revoke_session(&user, &session.id).await.unwrap();
+    assert!(matches!(revoked.next().await, Some(SessionEvent::Revoked)));
+
+    auth.logout_everywhere(&user).await.unwrap();
+    assert!(matches!(revoked.next().await, Some(SessionEvent::Revoked)));
+    assert!(auth.validate_access(&second.access, Utc::now()).await.is_err());
+}
+
+#[actix_rt::test]
+async fn refresh_invalidates_refreshed_pair() -> () {
+    let config = (&*DEFAULT_CONFIG).clone();
+    let ctr: Container = init_app(&config).await;
+
+    let db: &dyn DBIf = ctr.resolve_ref();
+    trunc_collection(&db.get(), "users").await;
+    trunc_collection(&db.get(), "tokens").await;
+
+    let auth: &dyn AuthServiceIf = ctr.resolve_ref();
+
+    auth.register("User20004".to_string(), "321000".to_string()).await.unwrap();
+    let tokens = auth.login("User20004".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
+    let refreshed = auth.refresh_token(&tokens.refresh, Utc::now()).await.unwrap();
+
+    let result = auth.validate_access(&tokens.access, Utc::now()).await;
+    assert_eq!(result.map(|_|()), Err(AppError::unauthorized()));
+    assert!(auth.validate_access(&refreshed.access, Utc::now()).await.is_ok());
+
+    // refreshed pair is the same session
+    let user = auth.validate_access(&refreshed.access, Utc::now()).await.unwrap();
+    assert_eq!(auth.sessions(&user, &refreshed.access, Utc::now()).await.len(), 1);
+
+    let refreshed_again = auth.refresh_token(&refreshed.refresh, Utc::now()).await;
+    assert!(refreshed_again.is_ok());
+}
+
+#[actix_rt::test]
+async fn refresh_token_reuse_revokes_whole_family() -> () {
+    let config = (&*DEFAULT_CONFIG).clone();
+    let ctr: Container = init_app(&config).await;
+
+    let db: &dyn DBIf = ctr.resolve_ref();
+    trunc_collection(&db.get(), "users").await;
+    trunc_collection(&db.get(), "tokens").await;
+
+    let auth: &dyn AuthServiceIf = ctr.resolve_ref();
+
+    auth.register("User20005".to_string(), "321000".to_string()).await.unwrap();
+    let tokens = auth.login("User20005".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
+    let other = auth.login("User20005".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
+    let refreshed = auth.refresh_token(&tokens.refresh, Utc::now()).await.unwrap();
+
+    let result = auth.refresh_token(&tokens.refresh, Utc::now()).await;
+    assert_eq!(result.map(|_|()), Err(AppError::unauthorized()));
+
+    let result = auth.validate_access(&refreshed.access, Utc::now()).await;
+    assert_eq!(result.map(|_|()), Err(AppError::unauthorized()));
+    let result = auth.refresh_token(&refreshed.refresh, Utc::now()).await;
+    assert_eq!(result.map(|_|()), Err(AppError::unauthorized()));
+
+    // other session is another family
+    assert!(auth.validate_access(&other.access, Utc::now()).await.is_ok());
+}
+
+#[actix_rt::test]
+async fn only_token_hashes_stored() -> () {
+    let config = (&*DEFAULT_CONFIG).clone();
+    let ctr: Container = init_app(&config).await;
+
+    let db: &dyn DBIf = ctr.resolve_ref();
+    trunc_collection(&db.get(), "users").await;
+    trunc_collection(&db.get(), "tokens").await;
+
+    let auth: &dyn AuthServiceIf = ctr.resolve_ref();
+
+    auth.register("User20006".to_string(), "321000".to_string()).await.unwrap();
+    let tokens = auth.login("User20006".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
+
+    let stored: Document = db.get().collection("tokens").find_one(None, None).await.unwrap().unwrap();
+    assert!(stored.get("access").is_none());
+    assert!(stored.get("refresh").is_none());
+    assert_eq!(stored.get_str("access_hash").unwrap(), hash_token(&config.token_secret, &tokens.access));
+    assert_eq!(stored.get_str("refresh_hash").unwrap(), hash_token(&config.token_secret, &tokens.refresh));
+
+    // clients still use raw tokens
+    assert!(auth.validate_access(&tokens.access, Utc::now()).await.is_ok());
+    assert!(auth.refresh_token(&tokens.refresh, Utc::now()).await.is_ok());
+}
+
+#[actix_rt::test]
+async fn plain_tokens_migrated_to_hashes() -> () {
+    let config = (&*DEFAULT_CONFIG).clone();
+    let ctr: Container = init_app(&config).await;
+
+    let db: &dyn DBIf = ctr.resolve_ref();
+    trunc_collection(&db.get(), "users").await;
+    trunc_collection(&db.get(), "tokens").await;
+
+    let auth: &dyn AuthServiceIf = ctr.resolve_ref();
+
+    auth.register("User20007".to_string(), "321000".to_string()).await.unwrap();
+    let tokens = auth.login("User20007".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
+    let user = auth.validate_access(&tokens.access, Utc::now()).await.unwrap();
+
+    // pair stored before tokens were hashed
+    let now = Utc::now();
+    db.get()
+        .collection("tokens")
+        .insert_one(
+            doc! {
+                "access": "plain_access",
+                "refresh": "plain_refresh",
+                // lifetimes are stored as strings like chrono serializes them
+                "access_lifetime": (now + Duration::hours(1)).to_rfc3339(),
+                "refresh_lifetime": (now + Duration::days(1)).to_rfc3339(),
+                "created_at": now.to_rfc3339(),
+                "user_id": user.id.oid(),
+            },
+            None,
+        )
+        .await
+        .unwrap();
+
+    migrate(db.get(), &config.token_secret).await;
+    // second run changes nothing
+    migrate(db.get(), &config.token_secret).await;
+
+    let result = auth.validate_access("plain_access", Utc::now()).await;
+    assert_eq!(result.map(|u| u.username), Ok("User20007".to_string()));
+    assert!(auth.validate_access(&tokens.access, Utc::now()).await.is_ok());
+
+    let plain_left = db.get().collection("tokens").count_documents(doc! {"access": {"$exists": true}}, None).await.unwrap();
+    assert_eq!(plain_left, 0);
+
+    assert!(auth.refresh_token("plain_refresh", Utc::now()).await.is_ok());
+}
+
+#[actix_rt::test]
+async fn token_pairs_expire_with_refresh_token() -> () {
+    let config = (&*DEFAULT_CONFIG).clone();
+    let ctr: Container = init_app(&config).await;
+
+    let db: &dyn DBIf = ctr.resolve_ref();
+    trunc_collection(&db.get(), "users").await;
+    trunc_collection(&db.get(), "tokens").await;
+
+    let auth: &dyn AuthServiceIf = ctr.resolve_ref();
+
+    auth.register("User20009".to_string(), "321000".to_string()).await.unwrap();
+    let tokens = auth.login("User20009".to_string(), "321000".to_string(), SessionMeta::default(), Utc::now()).await.unwrap();
+    let refreshed = auth.refresh_token(&tokens.refresh, Utc::now()).await.unwrap();
+    let user = auth.validate_access(&refreshed.access, Utc::now()).await.unwrap();
+
+    // rotated pair expires along with the fresh one
+    let stored: Vec<Document> = db.get().collection("tokens").find(None, None).await.unwrap()
+        .map(|d| d.unwrap())
+        .collect()
+        .await;
+    assert_eq!(stored.len(), 2);
+    let rotated = stored.iter().find(|d| d.get_bool("rotated") == Ok(true)).unwrap();
+    assert_eq!(
+        rotated.get_datetime("expires_at").unwrap().timestamp_millis(),
+        tokens.refresh_lifetime.timestamp_millis()
+    );
+
+    // pair stored before expiry dates were added
+    let lifetime = Utc::now() + Duration::days(1);
+    db.get()
+        .collection("tokens")
+        .insert_one(
+            doc! {
+                "access_hash": hash_token(&config.token_secret, "old_access"),
+                "refresh_hash": hash_token(&config.token_secret, "old_refresh"),
+                "access_lifetime": Utc::now().to_rfc3339(),
+                "refresh_lifetime": lifetime.to_rfc3339(),
+                "created_at": Utc::now().to_rfc3339(),
+                "user_id": user.id.oid(),
+            },
+            None,
+        )
+        .await
+        .unwrap();
+
+    migrate(db.get(), &config.token_secret).await;
+
+    let old: Document = db.get().collection("tokens")
+        .find_one(doc! {"access_hash": hash_token(&config.token_secret, "old_access")}, None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        old.get_datetime("expires_at").unwrap().timestamp_millis(),
+        lifetime.timestamp_millis()
+    );
+}
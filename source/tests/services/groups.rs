@@ -1,9 +1,14 @@
-use crate::drop_and_setup_with_random_user;
+use crate::{drop_and_setup_with_random_user, DEFAULT_CONFIG};
+use chrono::{Duration, Utc};
+use futures::{FutureExt, StreamExt};
+use motor_back::channels::groups::{GroupEvent, GroupsChannelIf, SetKey};
 use motor_back::container::Container;
 use motor_back::db::DBIf;
 use motor_back::errors::AppError;
-use motor_back::handlers::groups::UserGroup;
+use motor_back::handlers::groups::{UserGroup, UserSet};
 use motor_back::handlers::Paging;
+use motor_back::mongo::migrations::migrate;
+use motor_back::repos::sets::SetsSortBy;
 use motor_back::repos::users::User;
 use motor_back::repos::Id;
 use motor_back::services::groups::{GroupsServiceIf, IntoSet, PAGING_MAX_LIMIT};
@@ -23,6 +28,7 @@ async fn can_not_get_groups_if_pagination_limit_too_big() {
                 offset: Some(0),
                 limit: Some(PAGING_MAX_LIMIT + 1),
             }),
+            Utc::now(),
         )
         .await;
 
@@ -41,7 +47,13 @@ async fn can_create_group_when_no_groups_created() {
     let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
 
     let group = groups_service
-        .create_group(user, "some group".to_string(), IntoSet::Default, None)
+        .create_group(
+            user,
+            "some group".to_string(),
+            IntoSet::Default,
+            None,
+            Utc::now(),
+        )
         .await
         .unwrap();
 
@@ -62,6 +74,7 @@ async fn default_group_set_empty_if_nothing_inserted_in_it() {
                 offset: Some(0),
                 limit: Some(100),
             }),
+            Utc::now(),
         )
         .await
         .unwrap();
@@ -82,6 +95,7 @@ async fn group_set_empty_if_nothing_inserted_in_it() {
                 offset: Some(0),
                 limit: Some(100),
             }),
+            Utc::now(),
         )
         .await
         .unwrap();
@@ -102,13 +116,14 @@ async fn pagination_params_returned_same_as_passed() {
                 offset: Some(19),
                 limit: Some(84),
             }),
+            Utc::now(),
         )
         .await
         .unwrap();
 
     assert_eq!(response.page_info.offset, 19);
     assert_eq!(response.page_info.limit, 84);
-    assert_eq!(response.page_info.total, None);
+    assert_eq!(response.page_info.total, Some(0));
 }
 
 #[actix_rt::test]
@@ -117,7 +132,13 @@ async fn groups_presented_after_insertion_in_default_set() {
     let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
 
     let inserted_group_0 = groups_service
-        .create_group(user.clone(), "group 0".to_string(), IntoSet::Default, None)
+        .create_group(
+            user.clone(),
+            "group 0".to_string(),
+            IntoSet::Default,
+            None,
+            Utc::now(),
+        )
         .await
         .unwrap();
 
@@ -127,6 +148,7 @@ async fn groups_presented_after_insertion_in_default_set() {
             "group 1".to_string(),
             IntoSet::Default,
             Some(inserted_group_0.clone().id),
+            Utc::now(),
         )
         .await
         .unwrap();
@@ -139,6 +161,7 @@ async fn groups_presented_after_insertion_in_default_set() {
                 offset: Some(0),
                 limit: Some(2),
             }),
+            Utc::now(),
         )
         .await
         .unwrap()
@@ -157,6 +180,901 @@ async fn groups_presented_after_insertion_in_default_set() {
     assert_eq!(group_1.order, inserted_group_1.order);
 }
 
+#[actix_rt::test]
+async fn total_groups_count_returned_in_page_info() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    for name in &["group 0", "group 1", "group 2"] {
+        groups_service
+            .create_group(
+                user.clone(),
+                name.to_string(),
+                IntoSet::Default,
+                None,
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+    }
+
+    let response = groups_service
+        .list_groups(
+            user,
+            IntoSet::Default,
+            Some(Paging {
+                offset: Some(1),
+                limit: Some(1),
+            }),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.objects.len(), 1);
+    assert_eq!(response.page_info.total, Some(3));
+}
+
+#[actix_rt::test]
+async fn error_if_group_with_same_name_exists_in_set() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    groups_service
+        .create_group(
+            user.clone(),
+            "group".to_string(),
+            IntoSet::Named("set".to_string()),
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+    let res = groups_service
+        .create_group(
+            user,
+            "group".to_string(),
+            IntoSet::Named("set".to_string()),
+            None,
+            Utc::now(),
+        )
+        .await;
+
+    assert_eq!(
+        res,
+        Err(AppError::validation("Group `group` already exists"))
+    );
+}
+
+#[actix_rt::test]
+async fn can_not_remove_group_twice() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let group = groups_service
+        .create_group(
+            user.clone(),
+            "200".to_string(),
+            IntoSet::Default,
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+    let removed_group = groups_service
+        .remove_group(user.clone(), group.id.clone(), Utc::now())
+        .await
+        .unwrap();
+    assert_eq!(removed_group.name, "200");
+
+    let res = groups_service
+        .remove_group(user, group.id.clone(), Utc::now())
+        .await;
+    assert_eq!(
+        res,
+        Err(AppError::validation(&format!(
+            "Group `{}` you are trying to remove not exists",
+            group.id
+        )))
+    );
+}
+
+#[actix_rt::test]
+async fn groups_ordering_recounted_after_removal() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let set = || IntoSet::Named("set".to_string());
+    let group_0 = groups_service
+        .create_group(user.clone(), "group 0".to_string(), set(), None, Utc::now())
+        .await
+        .unwrap();
+    let group_1 = groups_service
+        .create_group(
+            user.clone(),
+            "group 1".to_string(),
+            set(),
+            Some(group_0.id.clone()),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+    groups_service
+        .create_group(
+            user.clone(),
+            "group 2".to_string(),
+            set(),
+            Some(group_1.id.clone()),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+    groups_service
+        .remove_group(user.clone(), group_1.id.clone(), Utc::now())
+        .await
+        .unwrap();
+
+    for set in vec![set(), IntoSet::Default] {
+        let response = groups_service
+            .list_groups(
+                user.clone(),
+                set,
+                Some(Paging {
+                    offset: Some(0),
+                    limit: Some(10),
+                }),
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+
+        let groups: Vec<(String, i32)> = response
+            .objects
+            .into_iter()
+            .map(|g| (g.name, g.order))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![("group 0".to_string(), 0), ("group 2".to_string(), 1)]
+        );
+        assert_eq!(response.page_info.total, Some(2));
+    }
+}
+
+#[actix_rt::test]
+async fn groups_order_kept_when_inserted_many_times_at_same_place() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let first = groups_service
+        .create_group(
+            user.clone(),
+            "first".to_string(),
+            IntoSet::Default,
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+    // each group is inserted right after the first one, so keys between
+    // the first one and previously inserted group get longer until the set is rebalanced
+    for i in 0..100 {
+        let group = groups_service
+            .create_group(
+                user.clone(),
+                format!("group {}", i),
+                IntoSet::Default,
+                Some(first.id.clone()),
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(group.order, 1);
+    }
+
+    let names: Vec<String> = groups_service
+        .list_groups(
+            user,
+            IntoSet::Default,
+            Some(Paging {
+                offset: Some(0),
+                limit: Some(200),
+            }),
+            Utc::now(),
+        )
+        .await
+        .unwrap()
+        .objects
+        .into_iter()
+        .map(|g| g.name)
+        .collect();
+
+    let mut expected = vec!["first".to_string()];
+    expected.extend((0..100).rev().map(|i| format!("group {}", i)));
+    assert_eq!(names, expected);
+}
+
+#[actix_rt::test]
+async fn group_moved_after_another_group() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let set = || IntoSet::Named("set".to_string());
+    let group_0 = groups_service
+        .create_group(user.clone(), "group 0".to_string(), set(), None, Utc::now())
+        .await
+        .unwrap();
+    let group_1 = groups_service
+        .create_group(
+            user.clone(),
+            "group 1".to_string(),
+            set(),
+            Some(group_0.id.clone()),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+    let group_2 = groups_service
+        .create_group(
+            user.clone(),
+            "group 2".to_string(),
+            set(),
+            Some(group_1.id.clone()),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+    let moved = groups_service
+        .move_group(
+            user.clone(),
+            group_0.id.clone(),
+            set(),
+            Some(group_2.id),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(moved.id_in_set, group_0.id_in_set);
+    assert_eq!(moved.order, 2);
+
+    let names: Vec<String> = groups_service
+        .list_groups(
+            user.clone(),
+            set(),
+            Some(Paging {
+                offset: Some(0),
+                limit: Some(10),
+            }),
+            Utc::now(),
+        )
+        .await
+        .unwrap()
+        .objects
+        .into_iter()
+        .map(|g| g.name)
+        .collect();
+    assert_eq!(names, vec!["group 1", "group 2", "group 0"]);
+
+    let moved = groups_service
+        .move_group(user, group_0.id.clone(), set(), None, Utc::now())
+        .await
+        .unwrap();
+    assert_eq!(moved.order, 0);
+}
+
+#[actix_rt::test]
+async fn group_moved_to_other_set() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let from = || IntoSet::Named("from".to_string());
+    let to = || IntoSet::Named("to".to_string());
+    let group = groups_service
+        .create_group(user.clone(), "group".to_string(), from(), None, Utc::now())
+        .await
+        .unwrap();
+    let to_group_0 = groups_service
+        .create_group(
+            user.clone(),
+            "to group 0".to_string(),
+            to(),
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+    groups_service
+        .create_group(
+            user.clone(),
+            "to group 1".to_string(),
+            to(),
+            Some(to_group_0.id.clone()),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+    let moved = groups_service
+        .move_group(
+            user.clone(),
+            group.id.clone(),
+            to(),
+            Some(to_group_0.id),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(moved.id_in_set, group.id_in_set);
+    assert_eq!(moved.order, 1);
+
+    let list = |set: IntoSet| {
+        groups_service.list_groups(
+            user.clone(),
+            set,
+            Some(Paging {
+                offset: Some(0),
+                limit: Some(10),
+            }),
+            Utc::now(),
+        )
+    };
+    let from_names: Vec<String> = list(from())
+        .await
+        .unwrap()
+        .objects
+        .into_iter()
+        .map(|g| g.name)
+        .collect();
+    assert!(from_names.is_empty());
+
+    let to_names: Vec<String> = list(to())
+        .await
+        .unwrap()
+        .objects
+        .into_iter()
+        .map(|g| g.name)
+        .collect();
+    assert_eq!(to_names, vec!["to group 0", "group", "to group 1"]);
+}
+
+#[actix_rt::test]
+async fn error_if_group_with_same_name_exists_in_set_group_moved_to() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let group = groups_service
+        .create_group(
+            user.clone(),
+            "group".to_string(),
+            IntoSet::Named("from".to_string()),
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+    groups_service
+        .create_group(
+            user.clone(),
+            "group".to_string(),
+            IntoSet::Named("to".to_string()),
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+    let res = groups_service
+        .move_group(
+            user,
+            group.id,
+            IntoSet::Named("to".to_string()),
+            None,
+            Utc::now(),
+        )
+        .await;
+
+    assert_eq!(
+        res,
+        Err(AppError::validation("Group `group` already exists"))
+    );
+}
+
+#[actix_rt::test]
+async fn set_renamed_with_its_groups() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let set = groups_service
+        .create_set(user.clone(), "old".to_string(), Utc::now())
+        .await
+        .unwrap();
+    groups_service
+        .create_group(
+            user.clone(),
+            "group".to_string(),
+            IntoSet::Named("old".to_string()),
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+    let renamed = groups_service
+        .rename_set(user.clone(), set.id.clone(), "new".to_string(), Utc::now())
+        .await
+        .unwrap();
+    assert_eq!(renamed.id, set.id);
+    assert_eq!(renamed.name, "new");
+
+    let list = |name: &str| {
+        groups_service.list_groups(
+            user.clone(),
+            IntoSet::Named(name.to_string()),
+            Some(Paging {
+                offset: Some(0),
+                limit: Some(10),
+            }),
+            Utc::now(),
+        )
+    };
+    assert!(list("old").await.unwrap().objects.is_empty());
+    let names: Vec<String> = list("new")
+        .await
+        .unwrap()
+        .objects
+        .into_iter()
+        .map(|g| g.name)
+        .collect();
+    assert_eq!(names, vec!["group"]);
+
+    let recents = groups_service.recent_sets(user).await;
+    assert_eq!(recents, vec![renamed]);
+}
+
+#[actix_rt::test]
+async fn error_if_set_renamed_to_existing_name() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let set = groups_service
+        .create_set(user.clone(), "set 0".to_string(), Utc::now())
+        .await
+        .unwrap();
+    groups_service
+        .create_set(user.clone(), "set 1".to_string(), Utc::now())
+        .await
+        .unwrap();
+
+    let res = groups_service
+        .rename_set(user, set.id, "set 1".to_string(), Utc::now())
+        .await;
+
+    assert_eq!(res, Err(AppError::validation("set with same name exists")));
+}
+
+#[actix_rt::test]
+async fn error_if_set_renamed_to_name_of_not_created_set() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let set = groups_service
+        .create_set(user.clone(), "set".to_string(), Utc::now())
+        .await
+        .unwrap();
+    groups_service
+        .create_group(
+            user.clone(),
+            "group".to_string(),
+            IntoSet::Named("not created".to_string()),
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+    let res = groups_service
+        .rename_set(user, set.id, "not created".to_string(), Utc::now())
+        .await;
+
+    assert_eq!(res, Err(AppError::validation("set with same name exists")));
+}
+
+#[actix_rt::test]
+async fn set_deleted_and_its_groups_kept_in_default_set() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let set = groups_service
+        .create_set(user.clone(), "set".to_string(), Utc::now())
+        .await
+        .unwrap();
+    groups_service
+        .create_group(
+            user.clone(),
+            "group".to_string(),
+            IntoSet::Named("set".to_string()),
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+    let deleted = groups_service
+        .delete_set(user.clone(), set.id.clone())
+        .await
+        .unwrap();
+    assert_eq!(deleted, set);
+
+    let list = |set: IntoSet| {
+        groups_service.list_groups(
+            user.clone(),
+            set,
+            Some(Paging {
+                offset: Some(0),
+                limit: Some(10),
+            }),
+            Utc::now(),
+        )
+    };
+    assert!(list(IntoSet::Named("set".to_string()))
+        .await
+        .unwrap()
+        .objects
+        .is_empty());
+    let names: Vec<String> = list(IntoSet::Default)
+        .await
+        .unwrap()
+        .objects
+        .into_iter()
+        .map(|g| g.name)
+        .collect();
+    assert_eq!(names, vec!["group"]);
+
+    assert!(groups_service.recent_sets(user.clone()).await.is_empty());
+    assert_eq!(
+        groups_service.delete_set(user, set.id).await,
+        Err(AppError::not_found(
+            "Set you are trying to delete not exists"
+        ))
+    );
+}
+
+#[actix_rt::test]
+async fn group_renamed_in_every_set_it_is_in() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let group = groups_service
+        .create_group(
+            user.clone(),
+            "old".to_string(),
+            IntoSet::Named("set".to_string()),
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+    let renamed = groups_service
+        .rename_group(
+            user.clone(),
+            group.id.clone(),
+            "new".to_string(),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(renamed.id, group.id);
+    assert_eq!(renamed.name, "new");
+
+    for set in vec![IntoSet::Default, IntoSet::Named("set".to_string())] {
+        let names: Vec<String> = groups_service
+            .list_groups(
+                user.clone(),
+                set,
+                Some(Paging {
+                    offset: Some(0),
+                    limit: Some(10),
+                }),
+                Utc::now(),
+            )
+            .await
+            .unwrap()
+            .objects
+            .into_iter()
+            .map(|g| g.name)
+            .collect();
+        assert_eq!(names, vec!["new"]);
+    }
+}
+
+#[actix_rt::test]
+async fn error_if_group_renamed_to_existing_name() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let group = groups_service
+        .create_group(
+            user.clone(),
+            "group 0".to_string(),
+            IntoSet::Default,
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+    groups_service
+        .create_group(
+            user.clone(),
+            "group 1".to_string(),
+            IntoSet::Default,
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+    let res = groups_service
+        .rename_group(
+            user.clone(),
+            group.id.clone(),
+            "group 1".to_string(),
+            Utc::now(),
+        )
+        .await;
+    assert_eq!(
+        res,
+        Err(AppError::validation("Group `group 1` already exists"))
+    );
+
+    let same = groups_service
+        .rename_group(user, group.id, "group 0".to_string(), Utc::now())
+        .await
+        .unwrap();
+    assert_eq!(same.name, "group 0");
+}
+
+#[actix_rt::test]
+async fn group_rename_sent_to_subscribers_of_its_sets() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+    let channel: &dyn GroupsChannelIf = ctr.resolve_ref();
+
+    let set_key = |set_name: Option<&str>| SetKey {
+        user_id: user.id.clone(),
+        set_name: set_name.map(|s| s.to_string()),
+    };
+    let mut default_set = channel.subscribe(&set_key(None));
+    let mut named_set = channel.subscribe(&set_key(Some("set")));
+    let mut other_set = channel.subscribe(&set_key(Some("other")));
+
+    let group = groups_service
+        .create_group(
+            user.clone(),
+            "old".to_string(),
+            IntoSet::Named("set".to_string()),
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+    groups_service
+        .rename_group(user, group.id.clone(), "new".to_string(), Utc::now())
+        .await
+        .unwrap();
+
+    for set in vec![&mut default_set, &mut named_set] {
+        match set.next().await.unwrap() {
+            GroupEvent::Updated(updated) => {
+                assert_eq!(updated.id, group.id);
+                assert_eq!(updated.name, "new");
+            }
+        }
+    }
+    assert!(other_set.next().now_or_never().is_none());
+}
+
+#[actix_rt::test]
+async fn recent_sets_sorted_by_last_use_and_capped() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let now = Utc::now();
+    let mut sets = vec![];
+    for i in 0..11 {
+        let set = groups_service
+            .create_set(
+                user.clone(),
+                format!("set {}", i),
+                now + Duration::seconds(i),
+            )
+            .await
+            .unwrap();
+        sets.push(set);
+    }
+
+    let recents = groups_service.recent_sets(user.clone()).await;
+    let expected: Vec<UserSet> = sets.iter().skip(1).rev().cloned().collect();
+    assert_eq!(recents, expected);
+
+    // opening a set moves it to the top
+    groups_service
+        .list_groups(
+            user.clone(),
+            IntoSet::Named("set 5".to_string()),
+            Some(Paging {
+                offset: Some(0),
+                limit: Some(10),
+            }),
+            now + Duration::seconds(20),
+        )
+        .await
+        .unwrap();
+    let recents = groups_service.recent_sets(user.clone()).await;
+    assert_eq!(recents.len(), 10);
+    assert_eq!(recents[0], sets[5]);
+    assert_eq!(recents[1], sets[10]);
+
+    // modifying a set too
+    groups_service
+        .create_group(
+            user.clone(),
+            "group".to_string(),
+            IntoSet::Named("set 7".to_string()),
+            None,
+            now + Duration::seconds(21),
+        )
+        .await
+        .unwrap();
+    let recents = groups_service.recent_sets(user).await;
+    assert_eq!(recents[0], sets[7]);
+    assert_eq!(recents[1], sets[5]);
+}
+
+#[actix_rt::test]
+async fn all_user_sets_listed_sorted_and_filtered() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    for name in vec!["Work", "home", "work.old", "Books"] {
+        groups_service
+            .create_set(user.clone(), name.to_string(), Utc::now())
+            .await
+            .unwrap();
+    }
+
+    let names = |sets: Paged<UserSet>| -> Vec<String> {
+        sets.objects.into_iter().map(|s| s.name).collect()
+    };
+    let paging = |offset: i32, limit: i32| {
+        Some(Paging {
+            offset: Some(offset),
+            limit: Some(limit),
+        })
+    };
+
+    let created = groups_service
+        .list_sets(user.clone(), paging(1, 2), SetsSortBy::CreatedAsc, None)
+        .await
+        .unwrap();
+    assert_eq!(created.page_info.total, Some(4));
+    assert_eq!(names(created), vec!["home", "work.old"]);
+
+    let newest = groups_service
+        .list_sets(user.clone(), paging(0, 10), SetsSortBy::CreatedDesc, None)
+        .await
+        .unwrap();
+    assert_eq!(names(newest), vec!["Books", "work.old", "home", "Work"]);
+
+    let filtered = groups_service
+        .list_sets(
+            user.clone(),
+            paging(0, 10),
+            SetsSortBy::NameDesc,
+            Some("WORK".to_string()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(filtered.page_info.total, Some(2));
+    assert_eq!(names(filtered), vec!["work.old", "Work"]);
+
+    let literal = groups_service
+        .list_sets(
+            user,
+            paging(0, 10),
+            SetsSortBy::NameAsc,
+            Some(".".to_string()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(names(literal), vec!["work.old"]);
+}
+
+#[actix_rt::test]
+async fn legacy_group_orders_migrated_to_order_keys() {
+    let (ctr, user): (Container, User) = drop_and_setup_with_random_user().await;
+    let groups_service: &dyn GroupsServiceIf = ctr.resolve_ref();
+    let db: &dyn DBIf = ctr.resolve_ref();
+
+    let legacy_set = || IntoSet::Named("legacy".to_string());
+    for name in &["a", "b", "c"] {
+        groups_service
+            .create_group(
+                user.clone(),
+                name.to_string(),
+                legacy_set(),
+                None,
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+    }
+
+    let list = |set: IntoSet| {
+        groups_service.list_groups(
+            user.clone(),
+            set,
+            Some(Paging {
+                offset: Some(0),
+                limit: Some(10),
+            }),
+            Utc::now(),
+        )
+    };
+    let names = |groups: &Vec<UserGroup>| groups.iter().map(|g| g.name.clone()).collect::<Vec<_>>();
+
+    // groups stored before order keys, numbered in reverse order
+    for (collection, set) in vec![
+        ("group_sets", legacy_set()),
+        ("default_group_sets", IntoSet::Default),
+    ] {
+        let groups = list(set).await.unwrap().objects;
+        for (i, group) in groups.iter().rev().enumerate() {
+            db.get()
+                .collection(collection)
+                .update_one(
+                    doc! {"_id": group.id_in_set.oid()},
+                    doc! {"$set": {"order": i as i32}, "$unset": {"order_key": ""}},
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+    }
+
+    migrate(db.get(), &DEFAULT_CONFIG.token_secret).await;
+
+    let named = list(legacy_set()).await.unwrap().objects;
+    assert_eq!(names(&named), vec!["c", "b", "a"]);
+    let default = list(IntoSet::Default).await.unwrap().objects;
+    assert_eq!(names(&default), vec!["c", "b", "a"]);
+
+    let legacy_left = db
+        .get()
+        .collection("group_sets")
+        .count_documents(doc! {"order": {"$exists": true}}, None)
+        .await
+        .unwrap();
+    assert_eq!(legacy_left, 0);
+
+    // keys continue to work for new groups
+    groups_service
+        .create_group(
+            user.clone(),
+            "d".to_string(),
+            legacy_set(),
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(list(legacy_set()).await.unwrap().objects.len(), 4);
+}
+
 // TODO
 // Тест на добавление группы в разные сеты
 // Чо делать когда одна группа в нескольких сетах, сколько раз она в дефолтном сете
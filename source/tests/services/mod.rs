@@ -1,3 +1,4 @@
 mod auth;
 mod stack;
-mod groups;
\ No newline at end of file
+mod groups;
+mod ordering;
\ No newline at end of file
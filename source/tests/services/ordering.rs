@@ -0,0 +1,87 @@
+use motor_back::services::ordering::{key_between, spread_keys, MAX_ORDER_KEY_LEN};
+
+fn assert_between(before: Option<&str>, after: Option<&str>) -> String {
+    let key = key_between(before, after);
+    assert!(!key.is_empty());
+    assert!(!key.ends_with('0'), "`{}` has trailing zero", key);
+    if let Some(before) = before {
+        assert!(before < key.as_str(), "`{}` is not after `{}`", key, before);
+    }
+    if let Some(after) = after {
+        assert!(key.as_str() < after, "`{}` is not before `{}`", key, after);
+    }
+    key
+}
+
+#[test]
+fn key_between_neighbours() {
+    assert_between(None, None);
+    assert_between(Some("V"), None);
+    assert_between(None, Some("V"));
+    assert_between(Some("1"), Some("2"));
+    assert_between(Some("1"), Some("11"));
+    assert_between(Some("z"), None);
+    assert_between(None, Some("01"));
+    assert_between(Some("0z"), Some("1"));
+    assert_between(Some("1zz"), Some("2"));
+
+    // adjacent keys get the midpoint, not the key closest to `before`
+    assert_eq!(key_between(Some("1"), Some("2")), "1V");
+}
+
+#[test]
+fn repeated_inserts_at_same_place_grow_keys_slowly() {
+    let first = "1".to_string();
+    let mut next = "2".to_string();
+    // group inserted right after the first one again and again
+    for i in 0..50 {
+        next = assert_between(Some(&first), Some(&next));
+        assert!(
+            next.len() <= MAX_ORDER_KEY_LEN,
+            "key `{}` is too long after {} inserts",
+            next,
+            i + 1
+        );
+    }
+
+    let mut last = "1".to_string();
+    // group inserted right before the last one again and again
+    for _ in 0..50 {
+        last = assert_between(Some(&last), Some("2"));
+        assert!(last.len() <= MAX_ORDER_KEY_LEN);
+    }
+}
+
+#[test]
+fn appended_and_prepended_keys_grow_slowly() {
+    let mut last = key_between(None, None);
+    let mut first = last.clone();
+    for _ in 0..100 {
+        last = assert_between(Some(&last), None);
+        first = assert_between(None, Some(&first));
+    }
+
+    // about one digit per 30 groups
+    assert!(last.len() <= 4);
+    assert!(first.len() <= 4);
+}
+
+#[test]
+fn keys_spread_evenly() {
+    assert!(spread_keys(0).is_empty());
+
+    for n in &[1, 2, 61, 62, 1000, 5000] {
+        let keys = spread_keys(*n);
+        assert_eq!(keys.len(), *n);
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        assert!(keys.iter().all(|k| !k.is_empty() && !k.ends_with('0')));
+        assert!(keys.iter().all(|k| k.len() <= 3));
+
+        // there is room to insert between and around spread keys
+        assert_between(None, Some(&keys[0]));
+        assert_between(Some(&keys[n - 1]), None);
+        for w in keys.windows(2) {
+            assert_between(Some(&w[0]), Some(&w[1]));
+        }
+    }
+}
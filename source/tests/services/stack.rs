@@ -1,291 +1,1028 @@
+use chrono::Utc;
+use futures::{FutureExt, StreamExt};
 use shaku::HasComponent;
 
+use motor_back::channels::stack::{StackChannelIf, StackItemEvent};
 use motor_back::container::Container;
 use motor_back::db::DBIf;
 use motor_back::errors::AppError;
 use motor_back::handlers::stack::{
-    NewBlock, NewMark, NewStackItem, StackItemChangeSet, UpdateBlock,
+    ChangeMark, InsertBlock, InsertChangeSet, NewBlock, NewMark, NewStackItem, StackItemChangeSet,
+    UpdateBlock,
 };
-use motor_back::logger::AppLoggerIf;
+use motor_back::handlers::Paging as HandlerPaging;
+use motor_back::repos::marks::MarkKind;
 use motor_back::repos::users::User;
+use motor_back::repos::Id;
+use motor_back::services::groups::{GroupsServiceIf, IntoSet};
+use motor_back::services::marks::find_words;
 use motor_back::services::stack::StackServiceIf;
+use motor_back::services::Paging;
 
 use crate::{setup_with_random_user, trunc_collection};
 
-// #[actix_rt::test]
-// async fn can_not_add_emtpy_item() {
-//     let (ctr, user): (Container, User) = setup_with_random_user().await;
-//     let stack: &dyn StackServiceIf = ctr.resolve_ref();
-//     let result = stack
-//         .add_to_my_stack(user, NewStackItem { blocks: vec![] })
-//         .await;
-//
-//     assert_eq!(
-//         result.map(|_| ()),
-//         Err(AppError::validation("Can not add empty stack item"))
-//     );
-// }
-//
-// #[actix_rt::test]
-// async fn item_with_empty_block_added() {
-//     let (ctr, user): (Container, User) = setup_with_random_user().await;
-//     let stack: &dyn StackServiceIf = ctr.resolve_ref();
-//     let result = stack
-//         .add_to_my_stack(
-//             user,
-//             NewStackItem {
-//                 blocks: vec![NewBlock {
-//                     text: "".to_string(),
-//                     marks: vec![],
-//                 }],
-//             },
-//         )
-//         .await
-//         .unwrap();
-//
-//     assert_eq!(result.blocks.len(), 1 as usize);
-// }
-//
-// #[actix_rt::test]
-// async fn error_if_orders_duplicated() {
-//     let (ctr, user): (Container, User) = setup_with_random_user().await;
-//     let stack: &dyn StackServiceIf = ctr.resolve_ref();
-//     let _result = stack
-//         .add_to_my_stack(
-//             user,
-//             NewStackItem {
-//                 blocks: vec![
-//                     NewBlock {
-//                         text: "".to_string(),
-//                         marks: vec![],
-//                     },
-//                     NewBlock {
-//                         text: "".to_string(),
-//                         marks: vec![],
-//                     },
-//                 ],
-//             },
-//         )
-//         .await;
-//
-//     // assert_eq!(
-//     //     result.map(|_| ()),
-//     //     Err(AppError::validation("Duplicated orders occurred"))
-//     // );
-// }
-//
-// #[actix_rt::test]
-// async fn orders_recounted_when_no_sequential() {
-//     let (ctr, user): (Container, User) = setup_with_random_user().await;
-//     let stack: &dyn StackServiceIf = ctr.resolve_ref();
-//
-//     let db: &dyn DBIf = ctr.resolve_ref();
-//     trunc_collection(&db.get(), "stack_history").await;
-//     trunc_collection(&db.get(), "marks").await;
-//     trunc_collection(&db.get(), "blocks").await;
-//     trunc_collection(&db.get(), "stacks").await;
-//
-//     // Add stacks item
-//     let result = stack
-//         .add_to_my_stack(
-//             user.clone(),
-//             NewStackItem {
-//                 blocks: vec![
-//                     NewBlock {
-//                         text: "Block 0".to_string(),
-//                         marks: vec![],
-//                     },
-//                     NewBlock {
-//                         text: "Block 1".to_string(),
-//                         marks: vec![],
-//                     },
-//                     NewBlock {
-//                         text: "Block 2 Hello! Hellllllo!!".to_string(),
-//                         marks: vec![],
-//                     },
-//                 ],
-//             },
-//         )
-//         .await
-//         .unwrap();
-//
-//     assert_eq!(0, result.blocks.get(0).unwrap().order);
-//     assert_eq!(1, result.blocks.get(1).unwrap().order);
-//     assert_eq!(2, result.blocks.get(2).unwrap().order);
-// }
-//
-// #[actix_rt::test]
-// async fn item_with_blocks_and_marks_added() {
-//     let (ctr, user): (Container, User) = setup_with_random_user().await;
-//     let stack: &dyn StackServiceIf = ctr.resolve_ref();
-//     let result = stack
-//         .add_to_my_stack(
-//             user,
-//             NewStackItem {
-//                 blocks: vec![NewBlock {
-//                     text: "Block!".to_string(),
-//                     marks: vec![NewMark { from: 0, to: 2 }],
-//                 }],
-//             },
-//         )
-//         .await
-//         .unwrap();
-//
-//     assert_eq!(result.blocks.len(), 1 as usize);
-//
-//     let first_block = result.blocks.get(0).unwrap();
-//     assert_eq!(first_block.text, "Block!");
-//     assert_eq!(first_block.marks.len(), 1 as usize);
-//
-//     let first_mark = first_block.marks.get(0).unwrap();
-//     assert_eq!(first_mark.from, 0);
-//     assert_eq!(first_mark.to, 2);
-// }
-//
-// #[actix_rt::test]
-// async fn error_id_deleted_and_updated_ids_intersects() {
-//     let (ctr, user): (Container, User) = setup_with_random_user().await;
-//     let stack: &dyn StackServiceIf = ctr.resolve_ref();
-//
-//     let db: &dyn DBIf = ctr.resolve_ref();
-//     trunc_collection(&db.get(), "stack_history").await;
-//     trunc_collection(&db.get(), "marks").await;
-//     trunc_collection(&db.get(), "blocks").await;
-//     trunc_collection(&db.get(), "stacks").await;
-//
-//     // Add stacks item
-//     let result = stack
-//         .add_to_my_stack(
-//             user.clone(),
-//             NewStackItem {
-//                 blocks: vec![
-//                     NewBlock {
-//                         text: "Block 0".to_string(),
-//                         marks: vec![],
-//                     },
-//                     NewBlock {
-//                         text: "Block 1".to_string(),
-//                         marks: vec![NewMark { from: 0, to: 2 }],
-//                     },
-//                     NewBlock {
-//                         text: "Block 2 Hello! Hellllllo!!".to_string(),
-//                         marks: vec![NewMark { from: 0, to: 2 }, NewMark { from: 4, to: 8 }],
-//                     },
-//                 ],
-//             },
-//         )
-//         .await
-//         .unwrap();
-//
-//     let result = stack
-//         .update_stack_item(
-//             user,
-//             StackItemChangeSet {
-//                 stack_id: result.id,
-//                 inserted: None,
-//                 removed: vec![result.blocks.get(1).unwrap().clone().id],
-//                 updated: vec![UpdateBlock {
-//                     id: result.blocks.get(1).unwrap().clone().id,
-//                     text: "123".to_string(),
-//                     marks: vec![],
-//                 }],
-//             },
-//         )
-//         .await;
-//
-//     assert_eq!(
-//         result.map(|_| ()),
-//         Err(AppError::validation(
-//             "updated and removed changes intersects"
-//         ))
-//     );
-// }
-//
-// #[actix_rt::test]
-// async fn upd() {
-//     let (ctr, user): (Container, User) = setup_with_random_user().await;
-//     let stack: &dyn StackServiceIf = ctr.resolve_ref();
-//
-//     let db: &dyn DBIf = ctr.resolve_ref();
-//     trunc_collection(&db.get(), "stack_history").await;
-//     trunc_collection(&db.get(), "marks").await;
-//     trunc_collection(&db.get(), "blocks").await;
-//     trunc_collection(&db.get(), "stacks").await;
-//
-//     // Add stacks item
-//     let result = stack
-//         .add_to_my_stack(
-//             user.clone(),
-//             NewStackItem {
-//                 blocks: vec![
-//                     NewBlock {
-//                         text: "Block 0".to_string(),
-//                         marks: vec![],
-//                     },
-//                     NewBlock {
-//                         text: "Block 1".to_string(),
-//                         marks: vec![NewMark { from: 0, to: 2 }],
-//                     },
-//                     NewBlock {
-//                         text: "Block 2 Hello! Hellllllo!!".to_string(),
-//                         marks: vec![NewMark { from: 0, to: 2 }, NewMark { from: 4, to: 8 }],
-//                     },
-//                 ],
-//             },
-//         )
-//         .await
-//         .unwrap();
-//
-//     let nn = stack
-//         .update_stack_item(
-//             user,
-//             StackItemChangeSet {
-//                 stack_id: result.id,
-//                 inserted: None,
-//                 removed: vec![result.blocks.get(1).unwrap().clone().id],
-//                 updated: vec![UpdateBlock {
-//                     id: result.blocks.get(2).unwrap().clone().id,
-//                     text: "123".to_string(),
-//                     marks: vec![],
-//                 }],
-//             },
-//         )
-//         .await;
-//
-//     println!("{:#?}", nn);
-//
-//     // println!("{:#?}", result);
-//
-//     // Update stacks item
-//     // let nn = stack.update_stack_item(
-//     //     user,
-//     //     UpdateStackItem {
-//     //         id: result.id,
-//     //         blocks: vec![
-//     //             UpdateBlock {
-//     //                 id: None,
-//     //                 text: "New Block Text".to_string(),
-//     //                 marks: vec![]
-//     //             },
-//     //             UpdateBlock {
-//     //                 id: Some(Id("123".to_string())),
-//     //                 text: "New Block Text".to_string(),
-//     //                 marks: vec![]
-//     //             },
-//     //             UpdateBlock {
-//     //                 id: Some(result.blocks.get(0).unwrap().clone().id),
-//     //                 text: "zzz".to_string(),
-//     //                 marks: vec![]
-//     //             }
-//     //         ],
-//     //     },
-//     // ).await;
-//
-//     // StackService::extract_new_blocks(stacks, )
-//
-//     // println!("{:#?}", nn);
-// }
-//
+#[actix_rt::test]
+async fn can_not_add_emtpy_item() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+    let result = stack
+        .add_to_my_stack(user, NewStackItem { blocks: vec![] })
+        .await;
+
+    assert_eq!(
+        result.map(|_| ()),
+        Err(AppError::validation("Can not add empty stack item"))
+    );
+}
+
+#[actix_rt::test]
+async fn item_with_empty_block_added() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+    let result = stack
+        .add_to_my_stack(
+            user,
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: "".to_string(),
+                    marks: vec![],
+                }],
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(result.blocks.len(), 1 as usize);
+}
+
+#[actix_rt::test]
+async fn orders_not_duplicated() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+    let result = stack
+        .add_to_my_stack(
+            user,
+            NewStackItem {
+                blocks: vec![
+                    NewBlock {
+                        text: "".to_string(),
+                        marks: vec![],
+                    },
+                    NewBlock {
+                        text: "".to_string(),
+                        marks: vec![],
+                    },
+                ],
+            },
+        )
+        .await
+        .unwrap();
+
+    // orders are given by blocks position, so equal blocks still have different ones
+    let orders: Vec<i32> = result.blocks.iter().map(|b| b.order).collect();
+    assert_eq!(orders, vec![0, 1]);
+}
+
+#[actix_rt::test]
+async fn orders_recounted_when_no_sequential() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let db: &dyn DBIf = ctr.resolve_ref();
+    trunc_collection(&db.get(), "stack_history").await;
+    trunc_collection(&db.get(), "marks").await;
+    trunc_collection(&db.get(), "blocks").await;
+    trunc_collection(&db.get(), "stack").await;
+
+    // Add stacks item
+    let result = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![
+                    NewBlock {
+                        text: "Block 0".to_string(),
+                        marks: vec![],
+                    },
+                    NewBlock {
+                        text: "Block 1".to_string(),
+                        marks: vec![],
+                    },
+                    NewBlock {
+                        text: "Block 2 Hello! Hellllllo!!".to_string(),
+                        marks: vec![],
+                    },
+                ],
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(0, result.blocks.get(0).unwrap().order);
+    assert_eq!(1, result.blocks.get(1).unwrap().order);
+    assert_eq!(2, result.blocks.get(2).unwrap().order);
+}
+
+#[actix_rt::test]
+async fn item_with_blocks_and_marks_added() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+    let result = stack
+        .add_to_my_stack(
+            user,
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: "Block!".to_string(),
+                    marks: vec![NewMark {
+                        from: 0,
+                        to: 2,
+                        kind: MarkKind::Bold,
+                        href: None,
+                        color: None,
+                    }],
+                }],
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(result.blocks.len(), 1 as usize);
+
+    let first_block = result.blocks.get(0).unwrap();
+    assert_eq!(first_block.text, "Block!");
+    assert_eq!(first_block.marks.len(), 1 as usize);
+
+    let first_mark = first_block.marks.get(0).unwrap();
+    assert_eq!(first_mark.from, 0);
+    assert_eq!(first_mark.to, 2);
+}
+
+#[actix_rt::test]
+async fn error_id_deleted_and_updated_ids_intersects() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let db: &dyn DBIf = ctr.resolve_ref();
+    trunc_collection(&db.get(), "stack_history").await;
+    trunc_collection(&db.get(), "marks").await;
+    trunc_collection(&db.get(), "blocks").await;
+    trunc_collection(&db.get(), "stack").await;
+
+    // Add stacks item
+    let result = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![
+                    NewBlock {
+                        text: "Block 0".to_string(),
+                        marks: vec![],
+                    },
+                    NewBlock {
+                        text: "Block 1".to_string(),
+                        marks: vec![NewMark {
+                            from: 0,
+                            to: 2,
+                            kind: MarkKind::Bold,
+                            href: None,
+                            color: None,
+                        }],
+                    },
+                    NewBlock {
+                        text: "Block 2 Hello! Hellllllo!!".to_string(),
+                        marks: vec![
+                            NewMark {
+                                from: 0,
+                                to: 2,
+                                kind: MarkKind::Bold,
+                                href: None,
+                                color: None,
+                            },
+                            NewMark {
+                                from: 4,
+                                to: 8,
+                                kind: MarkKind::Bold,
+                                href: None,
+                                color: None,
+                            },
+                        ],
+                    },
+                ],
+            },
+        )
+        .await
+        .unwrap();
+
+    let result = stack
+        .update_stack_item(
+            user,
+            StackItemChangeSet {
+                stack_id: result.id,
+                version: result.version,
+                inserted: None,
+                removed: vec![result.blocks.get(1).unwrap().clone().id],
+                updated: vec![UpdateBlock {
+                    id: result.blocks.get(1).unwrap().clone().id,
+                    text: "123".to_string(),
+                    marks: Some(vec![]),
+                }],
+            },
+        )
+        .await;
+
+    assert_eq!(
+        result.map(|_| ()),
+        Err(AppError::validation(
+            "updated and removed changes intersects"
+        ))
+    );
+}
+
+#[actix_rt::test]
+async fn removed_updated_and_inserted_blocks_applied() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let item = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![
+                    NewBlock {
+                        text: "Block 0".to_string(),
+                        marks: vec![],
+                    },
+                    NewBlock {
+                        text: "Block 1".to_string(),
+                        marks: vec![NewMark {
+                            from: 0,
+                            to: 2,
+                            kind: MarkKind::Bold,
+                            href: None,
+                            color: None,
+                        }],
+                    },
+                    NewBlock {
+                        text: "Block 2 Hello! Hellllllo!!".to_string(),
+                        marks: vec![
+                            NewMark {
+                                from: 0,
+                                to: 2,
+                                kind: MarkKind::Bold,
+                                href: None,
+                                color: None,
+                            },
+                            NewMark {
+                                from: 4,
+                                to: 8,
+                                kind: MarkKind::Bold,
+                                href: None,
+                                color: None,
+                            },
+                        ],
+                    },
+                ],
+            },
+        )
+        .await
+        .unwrap();
+
+    let block_0 = item.blocks.get(0).unwrap().clone();
+    let block_2 = item.blocks.get(2).unwrap().clone();
+    let kept_mark = block_2.marks.get(1).unwrap().clone();
+
+    let updated = stack
+        .update_stack_item(
+            user,
+            StackItemChangeSet {
+                stack_id: item.id.clone(),
+                version: item.version,
+                inserted: Some(InsertChangeSet {
+                    insert_after_id: Some(block_0.id.clone()),
+                    blocks: vec![InsertBlock {
+                        text: "Inserted".to_string(),
+                        marks: vec![ChangeMark {
+                            id: None,
+                            from: 1,
+                            to: 3,
+                            kind: MarkKind::Bold,
+                            href: None,
+                            color: None,
+                        }],
+                    }],
+                }),
+                removed: vec![item.blocks.get(1).unwrap().clone().id],
+                updated: vec![UpdateBlock {
+                    id: block_2.id.clone(),
+                    text: "Hello!".to_string(),
+                    marks: Some(vec![
+                        ChangeMark {
+                            id: Some(kept_mark.id.clone()),
+                            from: 0,
+                            to: 5,
+                            kind: MarkKind::Bold,
+                            href: None,
+                            color: None,
+                        },
+                        ChangeMark {
+                            id: None,
+                            from: 5,
+                            to: 6,
+                            kind: MarkKind::Italic,
+                            href: None,
+                            color: None,
+                        },
+                    ]),
+                }],
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(updated.blocks.len(), 3);
+
+    let first = updated.blocks.get(0).unwrap();
+    assert_eq!(first.id, block_0.id);
+    assert_eq!(first.order, 0);
+    assert_eq!(first.current_version, 0);
+
+    let inserted = updated.blocks.get(1).unwrap();
+    assert_eq!(inserted.text, "Inserted");
+    assert_eq!(inserted.order, 1);
+    assert_eq!(inserted.initial_version, 1);
+    assert_eq!(inserted.current_version, 1);
+    assert_eq!(inserted.marks.len(), 1);
+
+    let last = updated.blocks.get(2).unwrap();
+    assert_eq!(last.id, block_2.id);
+    assert_eq!(last.text, "Hello!");
+    assert_eq!(last.order, 2);
+    assert_eq!(last.initial_version, 0);
+    assert_eq!(last.current_version, 1);
+    assert_eq!(last.marks.len(), 2);
+    assert_eq!(last.marks.get(0).unwrap().id, kept_mark.id);
+    assert_eq!(last.marks.get(0).unwrap().to, 5);
+    assert_eq!(last.marks.get(1).unwrap().from, 5);
+}
+
+#[actix_rt::test]
+async fn error_if_all_blocks_removed() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let item = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: "Block 0".to_string(),
+                    marks: vec![],
+                }],
+            },
+        )
+        .await
+        .unwrap();
+
+    let result = stack
+        .update_stack_item(
+            user,
+            StackItemChangeSet {
+                stack_id: item.id.clone(),
+                version: item.version,
+                inserted: None,
+                removed: vec![item.blocks.get(0).unwrap().clone().id],
+                updated: vec![],
+            },
+        )
+        .await;
+
+    assert_eq!(
+        result.map(|_| ()),
+        Err(AppError::validation(
+            "Can not remove all blocks of stack item"
+        ))
+    );
+}
+
+#[actix_rt::test]
+async fn error_if_mark_not_from_updated_block() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let item = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![
+                    NewBlock {
+                        text: "Block 0".to_string(),
+                        marks: vec![NewMark {
+                            from: 0,
+                            to: 2,
+                            kind: MarkKind::Bold,
+                            href: None,
+                            color: None,
+                        }],
+                    },
+                    NewBlock {
+                        text: "Block 1".to_string(),
+                        marks: vec![],
+                    },
+                ],
+            },
+        )
+        .await
+        .unwrap();
+
+    let foreign_mark = item.blocks.get(0).unwrap().marks.get(0).unwrap().clone();
+    let block_1 = item.blocks.get(1).unwrap().clone();
+
+    let result = stack
+        .update_stack_item(
+            user,
+            StackItemChangeSet {
+                stack_id: item.id.clone(),
+                version: item.version,
+                inserted: None,
+                removed: vec![],
+                updated: vec![UpdateBlock {
+                    id: block_1.id.clone(),
+                    text: "Block 1".to_string(),
+                    marks: Some(vec![ChangeMark {
+                        id: Some(foreign_mark.id.clone()),
+                        from: 0,
+                        to: 1,
+                        kind: MarkKind::Bold,
+                        href: None,
+                        color: None,
+                    }]),
+                }],
+            },
+        )
+        .await;
+
+    assert_eq!(
+        result.map(|_| ()),
+        Err(AppError::validation(&format!(
+            "Mark `{}` not found in block `{}`",
+            foreign_mark.id, block_1.id
+        )))
+    );
+}
+
+#[actix_rt::test]
+async fn error_if_mark_out_of_block_text() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let new_block = |from: i32, to: i32| NewBlock {
+        text: "Block".to_string(),
+        marks: vec![NewMark {
+            from,
+            to,
+            kind: MarkKind::Bold,
+            href: None,
+            color: None,
+        }],
+    };
+
+    let result = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![new_block(0, 5), new_block(2, 6)],
+            },
+        )
+        .await;
+    assert_eq!(
+        result.map(|_| ()),
+        Err(AppError::field_validation(
+            "blocks.1.marks.0.to",
+            "Mark must end within block text"
+        ))
+    );
+
+    let result = stack
+        .add_to_my_stack(
+            user,
+            NewStackItem {
+                blocks: vec![new_block(3, 1)],
+            },
+        )
+        .await;
+    assert_eq!(
+        result.map(|_| ()),
+        Err(AppError::field_validation(
+            "blocks.0.marks.0.to",
+            "Mark must end after it starts"
+        ))
+    );
+}
+
+#[actix_rt::test]
+async fn mark_offsets_counted_in_utf16_code_units() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let new_block = |from: i32, to: i32| NewBlock {
+        // emoji takes 2 code units, so text is 13 code units long
+        text: "Привет 👋 мир".to_string(),
+        marks: vec![NewMark {
+            from,
+            to,
+            kind: MarkKind::Bold,
+            href: None,
+            color: None,
+        }],
+    };
+
+    let item = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![new_block(7, 9), new_block(10, 13)],
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(item.blocks.get(1).unwrap().marks.get(0).unwrap().to, 13);
+
+    let result = stack
+        .add_to_my_stack(
+            user,
+            NewStackItem {
+                blocks: vec![new_block(8, 13)],
+            },
+        )
+        .await;
+    assert_eq!(
+        result.map(|_| ()),
+        Err(AppError::field_validation(
+            "blocks.0.marks.0.from",
+            "Mark must not start inside a character"
+        ))
+    );
+}
+
+#[actix_rt::test]
+async fn marks_shifted_when_text_updated_without_marks() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let mark = |from: i32, to: i32| NewMark {
+        from,
+        to,
+        kind: MarkKind::Bold,
+        href: None,
+        color: None,
+    };
+
+    let item = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: "Привет 👋 мир".to_string(),
+                    marks: vec![mark(7, 9), mark(10, 13)],
+                }],
+            },
+        )
+        .await
+        .unwrap();
+    let block = item.blocks.get(0).unwrap().clone();
+
+    let update_text = |version: i32, text: &str| StackItemChangeSet {
+        stack_id: item.id.clone(),
+        version,
+        inserted: None,
+        removed: vec![],
+        updated: vec![UpdateBlock {
+            id: block.id.clone(),
+            text: text.to_string(),
+            marks: None,
+        }],
+    };
+
+    let updated = stack
+        .update_stack_item(user.clone(), update_text(0, "🙂 Привет 👋 мир"))
+        .await
+        .unwrap();
+    let ranges: Vec<(i32, i32)> = updated
+        .blocks
+        .get(0)
+        .unwrap()
+        .marks
+        .iter()
+        .map(|m| (m.from, m.to))
+        .collect();
+    assert_eq!(ranges, vec![(10, 12), (13, 16)]);
+
+    let updated = stack
+        .update_stack_item(user, update_text(1, "🙂 Привет 👋"))
+        .await
+        .unwrap();
+    let marks = &updated.blocks.get(0).unwrap().marks;
+    assert_eq!(marks.len(), 1);
+    assert_eq!(marks.get(0).unwrap().id, block.marks.get(0).unwrap().id);
+    assert_eq!(
+        (marks.get(0).unwrap().from, marks.get(0).unwrap().to),
+        (10, 12)
+    );
+}
+
+#[actix_rt::test]
+async fn error_if_link_mark_without_href() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let item = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: "Block".to_string(),
+                    marks: vec![],
+                }],
+            },
+        )
+        .await
+        .unwrap();
+
+    let result = stack
+        .update_stack_item(
+            user,
+            StackItemChangeSet {
+                stack_id: item.id.clone(),
+                version: item.version,
+                inserted: None,
+                removed: vec![],
+                updated: vec![UpdateBlock {
+                    id: item.blocks.get(0).unwrap().id.clone(),
+                    text: "Block".to_string(),
+                    marks: Some(vec![ChangeMark {
+                        id: None,
+                        from: 0,
+                        to: 5,
+                        kind: MarkKind::Link,
+                        href: None,
+                        color: None,
+                    }]),
+                }],
+            },
+        )
+        .await;
+
+    assert_eq!(
+        result.map(|_| ()),
+        Err(AppError::field_validation(
+            "updated.0.marks.0.href",
+            "Link mark must have `href`"
+        ))
+    );
+}
+
+#[actix_rt::test]
+async fn adjacent_and_overlapping_marks_of_same_kind_merged() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let mark = |from: i32, to: i32, kind: MarkKind| NewMark {
+        from,
+        to,
+        kind,
+        href: None,
+        color: None,
+    };
+
+    let item = stack
+        .add_to_my_stack(
+            user,
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: "Some bold and italic text".to_string(),
+                    marks: vec![
+                        mark(5, 9, MarkKind::Bold),
+                        mark(0, 5, MarkKind::Bold),
+                        mark(3, 12, MarkKind::Italic),
+                        mark(10, 20, MarkKind::Italic),
+                        mark(14, 20, MarkKind::Bold),
+                    ],
+                }],
+            },
+        )
+        .await
+        .unwrap();
+
+    let ranges: Vec<(i32, i32, MarkKind)> = item
+        .blocks
+        .get(0)
+        .unwrap()
+        .marks
+        .iter()
+        .map(|m| (m.from, m.to, m.kind))
+        .collect();
+    assert_eq!(
+        ranges,
+        vec![
+            (0, 9, MarkKind::Bold),
+            (3, 20, MarkKind::Italic),
+            (14, 20, MarkKind::Bold),
+        ]
+    );
+}
+
+#[actix_rt::test]
+async fn block_versions_listed_and_restored() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let item = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: "Version 0".to_string(),
+                    marks: vec![NewMark {
+                        from: 0,
+                        to: 7,
+                        kind: MarkKind::Bold,
+                        href: None,
+                        color: None,
+                    }],
+                }],
+            },
+        )
+        .await
+        .unwrap();
+    let block = item.blocks.get(0).unwrap().clone();
+
+    stack
+        .update_stack_item(
+            user.clone(),
+            StackItemChangeSet {
+                stack_id: item.id.clone(),
+                version: item.version,
+                inserted: None,
+                removed: vec![],
+                updated: vec![UpdateBlock {
+                    id: block.id.clone(),
+                    text: "Version 1".to_string(),
+                    marks: Some(vec![]),
+                }],
+            },
+        )
+        .await
+        .unwrap();
+
+    let history = stack
+        .block_history(user.clone(), block.id.clone())
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history.get(0).unwrap().version, 0);
+    assert_eq!(history.get(0).unwrap().text, "Version 0");
+    assert_eq!(history.get(0).unwrap().marks.len(), 1);
+
+    let restored = stack
+        .restore_block_version(user.clone(), block.id.clone(), 0)
+        .await
+        .unwrap();
+    let restored_block = restored.blocks.get(0).unwrap();
+    assert_eq!(restored_block.text, "Version 0");
+    assert_eq!(restored_block.current_version, 2);
+    assert_eq!(restored_block.marks.len(), 1);
+    assert_eq!(restored_block.marks.get(0).unwrap().to, 7);
+
+    let history = stack.block_history(user, block.id.clone()).await.unwrap();
+    let versions: Vec<i32> = history.iter().map(|v| v.version).collect();
+    assert_eq!(versions, vec![0, 1]);
+    assert_eq!(history.get(1).unwrap().text, "Version 1");
+}
+
+#[actix_rt::test]
+async fn mark_kinds_and_attributes_kept_in_block_and_history() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let item = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: "Link and highlight".to_string(),
+                    marks: vec![
+                        NewMark {
+                            from: 0,
+                            to: 4,
+                            kind: MarkKind::Link,
+                            href: Some("https://example.com".to_string()),
+                            color: None,
+                        },
+                        NewMark {
+                            from: 9,
+                            to: 18,
+                            kind: MarkKind::Highlight,
+                            href: None,
+                            color: Some("#ffff00".to_string()),
+                        },
+                    ],
+                }],
+            },
+        )
+        .await
+        .unwrap();
+    let block = item.blocks.get(0).unwrap().clone();
+    let link = block.marks.get(0).unwrap();
+    assert_eq!(link.kind, MarkKind::Link);
+    assert_eq!(link.href, Some("https://example.com".to_string()));
+    let highlight = block.marks.get(1).unwrap();
+    assert_eq!(highlight.kind, MarkKind::Highlight);
+    assert_eq!(highlight.color, Some("#ffff00".to_string()));
+
+    stack
+        .update_stack_item(
+            user.clone(),
+            StackItemChangeSet {
+                stack_id: item.id.clone(),
+                version: item.version,
+                inserted: None,
+                removed: vec![],
+                updated: vec![UpdateBlock {
+                    id: block.id.clone(),
+                    text: "Link and highlight".to_string(),
+                    marks: Some(vec![ChangeMark {
+                        id: Some(link.id.clone()),
+                        from: 0,
+                        to: 4,
+                        kind: MarkKind::Italic,
+                        href: None,
+                        color: None,
+                    }]),
+                }],
+            },
+        )
+        .await
+        .unwrap();
+
+    let my_stack = stack.my_stack(user.clone()).await;
+    let updated = my_stack.iter().find(|s| s.id == item.id).unwrap();
+    let updated_mark = updated.blocks.get(0).unwrap().marks.get(0).unwrap();
+    assert_eq!(updated_mark.kind, MarkKind::Italic);
+    assert_eq!(updated_mark.href, None);
+
+    let history = stack.block_history(user, block.id.clone()).await.unwrap();
+    assert_eq!(history.get(0).unwrap().marks, block.marks);
+}
+
+#[actix_rt::test]
+async fn can_not_see_history_of_someone_else_block() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let item = stack
+        .add_to_my_stack(
+            user,
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: "Block 0".to_string(),
+                    marks: vec![],
+                }],
+            },
+        )
+        .await
+        .unwrap();
+
+    let (_, other_user): (Container, User) = setup_with_random_user().await;
+    let result = stack
+        .block_history(other_user, item.blocks.get(0).unwrap().clone().id)
+        .await;
+
+    assert_eq!(result, Err(AppError::not_found("Block not found")));
+}
+
+#[actix_rt::test]
+async fn removed_item_moved_to_trash_and_restored() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let item = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: "Block 0".to_string(),
+                    marks: vec![],
+                }],
+            },
+        )
+        .await
+        .unwrap();
+
+    stack
+        .remove_from_my_stack(user.clone(), item.id.clone(), Utc::now())
+        .await
+        .unwrap();
+
+    assert!(!stack
+        .my_stack(user.clone())
+        .await
+        .iter()
+        .any(|i| i.id == item.id));
+    let trash = stack.trash(user.clone()).await;
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash.get(0).unwrap().id, item.id);
+
+    let restored = stack
+        .restore_from_trash(user.clone(), item.id.clone())
+        .await
+        .unwrap();
+    assert_eq!(restored.blocks.len(), 1);
+
+    assert!(stack
+        .my_stack(user.clone())
+        .await
+        .iter()
+        .any(|i| i.id == item.id));
+    assert_eq!(stack.trash(user).await.len(), 0);
+}
+
+#[actix_rt::test]
+async fn purged_item_deleted_with_blocks_marks_and_history() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+    let db: &dyn DBIf = ctr.resolve_ref();
+
+    let item = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: "Block 0".to_string(),
+                    marks: vec![NewMark {
+                        from: 0,
+                        to: 2,
+                        kind: MarkKind::Bold,
+                        href: None,
+                        color: None,
+                    }],
+                }],
+            },
+        )
+        .await
+        .unwrap();
+    let block = item.blocks.get(0).unwrap().clone();
+
+    stack
+        .update_stack_item(
+            user.clone(),
+            StackItemChangeSet {
+                stack_id: item.id.clone(),
+                version: item.version,
+                inserted: None,
+                removed: vec![],
+                updated: vec![UpdateBlock {
+                    id: block.id.clone(),
+                    text: "Block 0!".to_string(),
+                    marks: Some(vec![]),
+                }],
+            },
+        )
+        .await
+        .unwrap();
+
+    let result = stack.purge_from_trash(user.clone(), item.id.clone()).await;
+    assert_eq!(
+        result,
+        Err(AppError::not_found("Stack item not found in trash"))
+    );
+
+    stack
+        .remove_from_my_stack(user.clone(), item.id.clone(), Utc::now())
+        .await
+        .unwrap();
+    let purged_id = stack
+        .purge_from_trash(user.clone(), item.id.clone())
+        .await
+        .unwrap();
+    assert_eq!(purged_id, item.id);
+
+    assert_eq!(stack.trash(user).await.len(), 0);
+    let history_rows = db
+        .get()
+        .collection("stack_history")
+        .count_documents(doc! {"stack_id": item.id.oid()}, None)
+        .await
+        .unwrap();
+    assert_eq!(history_rows, 0);
+    let blocks = db
+        .get()
+        .collection("blocks")
+        .count_documents(doc! {"stack_id": item.id.oid()}, None)
+        .await
+        .unwrap();
+    assert_eq!(blocks, 0);
+}
+
 // // TODO тест - снапшот на такой то момент
 // // TODO тест на кукуху
 //
@@ -306,3 +1043,477 @@ use crate::{setup_with_random_user, trunc_collection};
 //     // ).await;
 //     // println!("{:#?}", bb);
 // }
+
+#[actix_rt::test]
+async fn stack_changes_sent_to_every_user_subscriber() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+    let channel: &dyn StackChannelIf = ctr.resolve_ref();
+
+    let mut first_tab = channel.subscribe(&user.id);
+    let mut second_tab = channel.subscribe(&user.id);
+    let mut other_user = channel.subscribe(&Id("other".to_string()));
+
+    let item = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: "Block".to_string(),
+                    marks: vec![],
+                }],
+            },
+        )
+        .await
+        .unwrap();
+    stack
+        .remove_from_my_stack(user, item.id.clone(), Utc::now())
+        .await
+        .unwrap();
+
+    for tab in vec![&mut first_tab, &mut second_tab] {
+        match tab.next().await.unwrap() {
+            StackItemEvent::Created(created) => assert_eq!(created.id, item.id),
+            e => panic!("unexpected event {:?}", e),
+        }
+        match tab.next().await.unwrap() {
+            StackItemEvent::Deleted(id) => assert_eq!(id, item.id),
+            e => panic!("unexpected event {:?}", e),
+        }
+    }
+    assert!(other_user.next().now_or_never().is_none());
+}
+
+#[actix_rt::test]
+async fn error_if_changes_made_against_outdated_version() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let item = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: "Block".to_string(),
+                    marks: vec![],
+                }],
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(item.version, 0);
+
+    let change_text = |text: &str| StackItemChangeSet {
+        stack_id: item.id.clone(),
+        version: item.version,
+        inserted: None,
+        removed: vec![],
+        updated: vec![UpdateBlock {
+            id: item.blocks.get(0).unwrap().id.clone(),
+            text: text.to_string(),
+            marks: Some(vec![]),
+        }],
+    };
+
+    let updated = stack
+        .update_stack_item(user.clone(), change_text("From phone"))
+        .await
+        .unwrap();
+    assert_eq!(updated.version, 1);
+
+    let result = stack
+        .update_stack_item(user.clone(), change_text("From laptop"))
+        .await;
+    assert_eq!(
+        result.map(|_| ()),
+        Err(AppError::conflict(
+            "Stack item was changed by someone else",
+            1
+        ))
+    );
+
+    let my_stack = stack.my_stack(user).await;
+    let item = my_stack.iter().find(|s| s.id == item.id).unwrap();
+    assert_eq!(item.blocks.get(0).unwrap().text, "From phone");
+}
+
+#[actix_rt::test]
+async fn stack_searched_by_blocks_text() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let add = |texts: Vec<&str>| {
+        stack.add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: texts
+                    .into_iter()
+                    .map(|t| NewBlock {
+                        text: t.to_string(),
+                        marks: vec![],
+                    })
+                    .collect(),
+            },
+        )
+    };
+
+    let found_item = add(vec!["Nothing here", "👋 Привет, мир! Мир"])
+        .await
+        .unwrap();
+    add(vec!["Hello world"]).await.unwrap();
+    let removed_item = add(vec!["Мир"]).await.unwrap();
+    stack
+        .remove_from_my_stack(user.clone(), removed_item.id, Utc::now())
+        .await
+        .unwrap();
+
+    let found = stack
+        .search_my_stack(
+            user.clone(),
+            "мир".to_string(),
+            Paging {
+                offset: 0,
+                limit: 10,
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(found.page_info.total, Some(1));
+    assert_eq!(found.objects.len(), 1);
+    let hit = found.objects.get(0).unwrap();
+    assert_eq!(hit.stack_item.id, found_item.id);
+    let matched_block_id = found_item.blocks.get(1).unwrap().id.clone();
+    let ranges: Vec<(Id, i32, i32)> = hit
+        .highlights
+        .iter()
+        .map(|h| (h.block_id.clone(), h.from, h.to))
+        .collect();
+    assert_eq!(
+        ranges,
+        vec![
+            (matched_block_id.clone(), 11, 14),
+            (matched_block_id, 16, 19)
+        ]
+    );
+
+    let result = stack
+        .search_my_stack(
+            user,
+            " ".to_string(),
+            Paging {
+                offset: 0,
+                limit: 10,
+            },
+        )
+        .await;
+    assert_eq!(
+        result.map(|_| ()),
+        Err(AppError::field_validation(
+            "query",
+            "Search query can not be empty"
+        ))
+    );
+}
+
+#[test]
+fn highlights_match_whole_words_only() {
+    // text search finds whole words, highlights must not mark them inside other words
+    let ranges = find_words("Concatenate the cat, CAT! cats", "cat");
+    assert_eq!(ranges, vec![(16, 19), (21, 24)]);
+
+    let ranges = find_words("Привет, мир! Мирный", "мир");
+    assert_eq!(ranges, vec![(8, 11)]);
+}
+
+#[test]
+fn negative_paging_clamped() {
+    let paging = Paging::from(Some(HandlerPaging {
+        offset: Some(-5),
+        limit: Some(-1),
+    }));
+
+    assert_eq!((paging.offset, paging.limit), (0, 0));
+}
+
+#[actix_rt::test]
+async fn stack_items_filed_into_group_in_order() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+    let groups: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let add = |text: &str| {
+        stack.add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: text.to_string(),
+                    marks: vec![],
+                }],
+            },
+        )
+    };
+    let item_0 = add("0").await.unwrap();
+    let item_1 = add("1").await.unwrap();
+    let item_2 = add("2").await.unwrap();
+
+    let group = groups
+        .create_group(
+            user.clone(),
+            "group".to_string(),
+            IntoSet::Default,
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+    let other_group = groups
+        .create_group(
+            user.clone(),
+            "other".to_string(),
+            IntoSet::Default,
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+    stack
+        .add_to_group(user.clone(), item_0.id.clone(), group.id.clone(), None)
+        .await
+        .unwrap();
+    stack
+        .add_to_group(
+            user.clone(),
+            item_2.id.clone(),
+            group.id.clone(),
+            Some(item_0.id.clone()),
+        )
+        .await
+        .unwrap();
+    let filed_item = stack
+        .add_to_group(
+            user.clone(),
+            item_1.id.clone(),
+            group.id.clone(),
+            Some(item_0.id.clone()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(filed_item.groups_ids, vec![group.id.clone()]);
+
+    let filed_item = stack
+        .add_to_group(
+            user.clone(),
+            item_1.id.clone(),
+            other_group.id.clone(),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(filed_item.groups_ids.len(), 2);
+
+    let group_items = |paging: Paging| stack.group_items(user.clone(), group.id.clone(), paging);
+    let ids: Vec<Id> = group_items(Paging {
+        offset: 0,
+        limit: 10,
+    })
+    .await
+    .unwrap()
+    .objects
+    .into_iter()
+    .map(|i| i.id)
+    .collect();
+    assert_eq!(
+        ids,
+        vec![item_0.id.clone(), item_1.id.clone(), item_2.id.clone()]
+    );
+
+    // moved to the group start
+    stack
+        .add_to_group(user.clone(), item_2.id.clone(), group.id.clone(), None)
+        .await
+        .unwrap();
+    stack
+        .remove_from_group(user.clone(), item_1.id.clone(), group.id.clone())
+        .await
+        .unwrap();
+
+    let page = group_items(Paging {
+        offset: 1,
+        limit: 10,
+    })
+    .await
+    .unwrap();
+    assert_eq!(page.page_info.total, Some(2));
+    let ids: Vec<Id> = page.objects.into_iter().map(|i| i.id).collect();
+    assert_eq!(ids, vec![item_0.id.clone()]);
+
+    // negative limit does not list the whole group
+    let page = group_items(Paging::from(Some(HandlerPaging {
+        offset: Some(0),
+        limit: Some(-1),
+    })))
+    .await
+    .unwrap();
+    assert_eq!(page.objects.len(), 0);
+    assert_eq!(page.page_info.limit, 0);
+    assert_eq!(page.page_info.total, Some(2));
+
+    let result = stack
+        .remove_from_group(user.clone(), item_1.id, group.id)
+        .await;
+    assert_eq!(
+        result.map(|_| ()),
+        Err(AppError::not_found("Stack item not found in group"))
+    );
+}
+
+#[actix_rt::test]
+async fn error_if_stack_item_filed_into_removed_group() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+    let groups: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let item = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: "text".to_string(),
+                    marks: vec![],
+                }],
+            },
+        )
+        .await
+        .unwrap();
+    let group = groups
+        .create_group(
+            user.clone(),
+            "group".to_string(),
+            IntoSet::Default,
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+    groups
+        .remove_group(user.clone(), group.id.clone(), Utc::now())
+        .await
+        .unwrap();
+
+    let result = stack.add_to_group(user, item.id, group.id, None).await;
+    assert_eq!(
+        result.map(|_| ()),
+        Err(AppError::not_found("Group not found"))
+    );
+}
+
+#[actix_rt::test]
+async fn stack_items_popped_in_lifo_order_into_group() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+    let groups: &dyn GroupsServiceIf = ctr.resolve_ref();
+
+    let add = |text: &str| {
+        stack.add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: text.to_string(),
+                    marks: vec![],
+                }],
+            },
+        )
+    };
+    let item_0 = add("0").await.unwrap();
+    let item_1 = add("1").await.unwrap();
+
+    let ids: Vec<Id> = stack
+        .my_stack(user.clone())
+        .await
+        .into_iter()
+        .map(|i| i.id)
+        .collect();
+    assert_eq!(ids, vec![item_1.id.clone(), item_0.id.clone()]);
+
+    let group = groups
+        .create_group(
+            user.clone(),
+            "group".to_string(),
+            IntoSet::Default,
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+    let top = stack.stack_peek(user.clone()).await.unwrap();
+    assert_eq!(top.id, item_1.id);
+
+    let popped = stack
+        .stack_pop(user.clone(), group.id.clone())
+        .await
+        .unwrap();
+    assert_eq!(popped.id, item_1.id);
+    assert_eq!(popped.groups_ids, vec![group.id.clone()]);
+
+    let top = stack.stack_peek(user.clone()).await.unwrap();
+    assert_eq!(top.id, item_0.id);
+
+    // popped item pushed back goes on top again
+    stack
+        .stack_push(user.clone(), item_1.id.clone(), Utc::now())
+        .await
+        .unwrap();
+    let top = stack.stack_peek(user.clone()).await.unwrap();
+    assert_eq!(top.id, item_1.id);
+
+    stack
+        .stack_pop(user.clone(), group.id.clone())
+        .await
+        .unwrap();
+    stack
+        .stack_pop(user.clone(), group.id.clone())
+        .await
+        .unwrap();
+    assert!(stack.stack_peek(user.clone()).await.is_none());
+
+    let result = stack.stack_pop(user.clone(), group.id.clone()).await;
+    assert_eq!(
+        result.map(|_| ()),
+        Err(AppError::not_found("Stack is empty"))
+    );
+
+    // popped items are still in user stack
+    assert_eq!(stack.my_stack(user).await.len(), 2);
+}
+
+#[actix_rt::test]
+async fn stack_item_in_trash_can_not_be_pushed() {
+    let (ctr, user): (Container, User) = setup_with_random_user().await;
+    let stack: &dyn StackServiceIf = ctr.resolve_ref();
+
+    let item = stack
+        .add_to_my_stack(
+            user.clone(),
+            NewStackItem {
+                blocks: vec![NewBlock {
+                    text: "Block".to_string(),
+                    marks: vec![],
+                }],
+            },
+        )
+        .await
+        .unwrap();
+    stack
+        .remove_from_my_stack(user.clone(), item.id.clone(), Utc::now())
+        .await
+        .unwrap();
+
+    let result = stack.stack_push(user, item.id, Utc::now()).await;
+    assert_eq!(
+        result.map(|_| ()),
+        Err(AppError::not_found("Stack item not found"))
+    );
+}